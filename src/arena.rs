@@ -0,0 +1,176 @@
+//generational arena, so handles to removed slots can't alias whatever reuses the slot later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+impl Handle {
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+#[derive(Debug)]
+pub struct Arena<T> {
+    entries: Vec<Entry<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Arena<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    //f gets the handle up front so values can store their own slot
+    pub fn insert_with<F>(&mut self, f: F) -> Handle
+    where
+        F: FnOnce(Handle) -> T,
+    {
+        let handle = if let Some(index) = self.free.pop() {
+            Handle {
+                index,
+                generation: self.entries[index as usize].generation,
+            }
+        } else {
+            self.entries.push(Entry {
+                generation: 0,
+                value: None,
+            });
+            Handle {
+                index: (self.entries.len() - 1) as u32,
+                generation: 0,
+            }
+        };
+        self.entries[handle.index()].value = Some(f(handle));
+        self.len += 1;
+        handle
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let entry = self.entries.get_mut(handle.index())?;
+        if entry.generation != handle.generation {
+            return None;
+        }
+        let value = entry.value.take()?;
+        entry.generation = entry.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        let entry = self.entries.get(handle.index())?;
+        if entry.generation != handle.generation {
+            return None;
+        }
+        entry.value.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        let entry = self.entries.get_mut(handle.index())?;
+        if entry.generation != handle.generation {
+            return None;
+        }
+        entry.value.as_mut()
+    }
+
+    pub fn get_by_index(&self, index: usize) -> Option<&T> {
+        self.entries.get(index)?.value.as_ref()
+    }
+
+    pub fn get_mut_by_index(&mut self, index: usize) -> Option<&mut T> {
+        self.entries.get_mut(index)?.value.as_mut()
+    }
+
+    pub fn handle_at(&self, index: usize) -> Option<Handle> {
+        let entry = self.entries.get(index)?;
+        entry.value.as_ref().map(|_| Handle {
+            index: index as u32,
+            generation: entry.generation,
+        })
+    }
+
+    //panics if either slot is empty or i==j
+    pub fn get2_mut_by_index(&mut self, i: usize, j: usize) -> (&mut T, &mut T) {
+        assert_ne!(i, j, "get2_mut_by_index called with the same slot twice");
+        let (lo, hi) = (i.min(j), i.max(j));
+        let (slice1, slice2) = self.entries.split_at_mut(hi);
+        let lo_value = slice1[lo].value.as_mut().expect("empty arena slot");
+        let hi_value = slice2[0].value.as_mut().expect("empty arena slot");
+        if i < j {
+            (lo_value, hi_value)
+        } else {
+            (hi_value, lo_value)
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().filter_map(|entry| entry.value.as_ref())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entries
+            .iter_mut()
+            .filter_map(|entry| entry.value.as_mut())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    //upper bound on slot indices, including holes
+    pub fn slot_count(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_handle() {
+        let mut arena = Arena::with_capacity(2);
+        let a = arena.insert_with(|_| 'a');
+        let b = arena.insert_with(|_| 'b');
+        assert_eq!(arena.remove(a), Some('a'));
+        assert_eq!(arena.get(a), None);
+        assert_eq!(arena.remove(a), None);
+
+        //reuses a's slot under a new generation
+        let c = arena.insert_with(|_| 'c');
+        assert_eq!(c.index(), a.index());
+        assert_ne!(c, a);
+        assert_eq!(arena.get(a), None);
+        assert_eq!(arena.get(c), Some(&'c'));
+        assert_eq!(arena.get(b), Some(&'b'));
+        assert_eq!(arena.len(), 2);
+        assert_eq!(arena.slot_count(), 2);
+    }
+
+    #[test]
+    fn test_iter_skips_holes() {
+        let mut arena = Arena::with_capacity(3);
+        let handles: Vec<Handle> = (0..3).map(|i| arena.insert_with(|_| i)).collect();
+        arena.remove(handles[1]);
+        assert_eq!(arena.iter().copied().collect::<Vec<_>>(), vec![0, 2]);
+        let (x, y) = arena.get2_mut_by_index(2, 0);
+        assert_eq!((*x, *y), (2, 0));
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Instant;
+
+use physics_engine::{
+    cli::Args,
+    stats::{StepStats, StepStatsWindow},
+    world::{World, WorldSettings},
+};
+
+type Metric = fn(&StepStats) -> f64;
+
+//runs the simulation without a window or gpu, e.g. for ci
+fn main() {
+    let args = Args::from_env();
+    let settings = WorldSettings {
+        solver_threads: args.threads,
+        ..Default::default()
+    };
+    let mut world = World::new(args.scene, args.count, settings);
+    let mut step_stats = StepStatsWindow::new(args.steps.max(1));
+    let start = Instant::now();
+    for _ in 0..args.steps {
+        step_stats.push(world.update());
+    }
+    let elapsed = start.elapsed();
+
+    let mut body_count = 0;
+    let mut max_speed: f32 = 0.0;
+    let mut height_sum = 0.0;
+    let mut kinetic_energy = 0.0;
+    for body in world.bodies() {
+        body_count += 1;
+        max_speed = max_speed.max(body.velocity.mag());
+        height_sum += body.position.y;
+        let inv_mass = body.inverse_mass;
+        if inv_mass > 0.0 {
+            kinetic_energy += 0.5 * body.velocity.dot(&body.velocity) / inv_mass;
+        }
+    }
+    println!("scene: {:?}", args.scene);
+    println!("bodies: {body_count}");
+    println!("steps: {}", args.steps);
+    println!("wall time: {} ms", elapsed.as_millis());
+    println!(
+        "mean step: {:.3} ms",
+        elapsed.as_secs_f64() * 1000.0 / args.steps.max(1) as f64
+    );
+    println!("max speed: {max_speed:.3} m/s");
+    println!(
+        "mean height: {:.3} m",
+        height_sum / body_count.max(1) as f32
+    );
+    println!("linear kinetic energy: {kinetic_energy:.3} J");
+
+    println!("{:>16} {:>10} {:>10} {:>10}", "", "min", "mean", "p99");
+    let rows: [(&str, Metric); 13] = [
+        ("total ms", |x| x.total_time.as_secs_f64() * 1000.0),
+        ("integrate ms", |x| x.integrate_time.as_secs_f64() * 1000.0),
+        ("grid init ms", |x| x.grid_init_time.as_secs_f64() * 1000.0),
+        ("ccd ms", |x| x.ccd_time.as_secs_f64() * 1000.0),
+        ("static ms", |x| x.static_time.as_secs_f64() * 1000.0),
+        ("broad ms", |x| x.broad_time.as_secs_f64() * 1000.0),
+        ("narrow ms", |x| x.narrow_time.as_secs_f64() * 1000.0),
+        ("impulse ms", |x| x.impulse_time.as_secs_f64() * 1000.0),
+        ("pair checks", |x| x.pair_checks as f64),
+        ("collisions", |x| x.collisions as f64),
+        ("contacts", |x| x.contacts as f64),
+        ("bodies awake", |x| x.bodies_awake as f64),
+        ("islands", |x| x.islands as f64),
+    ];
+    for (name, f) in rows {
+        if let Some(summary) = step_stats.summary(f) {
+            println!(
+                "{name:>16} {:>10.3} {:>10.3} {:>10.3}",
+                summary.min, summary.mean, summary.p99
+            );
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::scenes::{DEFAULT_N, Scene};
+
+pub const USAGE: &str = "options:
+  --scene <grid|slanted-tower|meteor|inverted-meteor|catapult|sticks|platforms|cube|balls|rocks|bowl|terrain|hopper|furniture|tumblers>
+  --count <number of bodies including static ones, minus floor>
+  --steps <number of ticks, headless only>
+  --threads <number of island solver workers>";
+
+//same flags for every binary so a scene can be reproduced with one command
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub scene: Scene,
+    pub count: usize,
+    pub steps: usize,
+    pub threads: usize,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            scene: Scene::Cube,
+            count: DEFAULT_N,
+            steps: 1000,
+            threads: 1,
+        }
+    }
+}
+
+impl Args {
+    //expects the program name to already be skipped
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut ans = Self::default();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {flag}"))?;
+            match flag.as_str() {
+                "--scene" => ans.scene = value.parse()?,
+                "--count" => {
+                    ans.count = value
+                        .parse()
+                        .map_err(|_| format!("invalid count: {value}"))?
+                }
+                "--steps" => {
+                    ans.steps = value
+                        .parse()
+                        .map_err(|_| format!("invalid steps: {value}"))?
+                }
+                "--threads" => {
+                    ans.threads = value
+                        .parse()
+                        .map_err(|_| format!("invalid threads: {value}"))?
+                }
+                _ => return Err(format!("unknown flag: {flag}")),
+            }
+        }
+        ans.scene.check_count(ans.count)?;
+        Ok(ans)
+    }
+
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(args) => args,
+            Err(err) => {
+                eprintln!("{err}\n{USAGE}");
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|x| x.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse(&["--scene", "meteor", "--count", "5000", "--steps", "2000"]),
+            Ok(Args {
+                scene: Scene::Meteor,
+                count: 5000,
+                steps: 2000,
+                threads: 1,
+            })
+        );
+        assert_eq!(parse(&[]), Ok(Args::default()));
+        assert!(parse(&["--scene", "teapot"]).is_err());
+        assert!(parse(&["--count"]).is_err());
+        assert!(parse(&["--count", "-1"]).is_err());
+        assert!(parse(&["--scene", "catapult", "--count", "7"]).is_err());
+        assert!(parse(&["--scene", "catapult", "--count", "8"]).is_ok());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{
+    math::{Mat3, Quaternion, Vec3},
+    shape::Shape,
+    world::AABB,
+};
+
+//one of a compound's shapes, placed relative to the compound's centre of mass
+#[derive(Debug, Clone, PartialEq)]
+pub struct Child {
+    pub shape: Shape,
+    pub scale: Vec3, //as for a body, cuboids only
+    pub position: Vec3,
+    pub rotation: Quaternion,
+}
+
+impl Default for Child {
+    fn default() -> Self {
+        Self {
+            shape: Shape::Cuboid,
+            scale: Vec3 {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+            position: Vec3::default(),
+            rotation: Quaternion::default(),
+        }
+    }
+}
+
+//several shapes moving as one rigid body, e.g. a table's top and legs. each child collides on
+//its own but the impulses all go to the body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compound {
+    pub children: Vec<Child>, //centred on the centre of mass
+    pub min: Vec3,            //local bounding box
+    pub max: Vec3,
+    pub volume: f32,
+    pub unit_inertia: Mat3, //about the centre of mass, for a mass of 1
+    pub bounding_radius: f32,
+    pub thinnest: f32, //smallest extent of any child, for ccd
+}
+
+impl Compound {
+    //the children are moved so the centre of mass ends up at the origin, like a hull's points,
+    //assuming they're all the same density. None if there are none, or any of them is static,
+    //another compound or has no volume
+    pub fn new(mut children: Vec<Child>) -> Option<Self> {
+        if children.is_empty()
+            || children.iter().any(|x| {
+                x.shape.is_static()
+                    || matches!(x.shape, Shape::Compound(_))
+                    || x.shape.volume(x.scale) <= 0.0
+            })
+        {
+            return None;
+        }
+        let volume: f32 = children.iter().map(|x| x.shape.volume(x.scale)).sum();
+        let centre = children
+            .iter()
+            .map(|x| x.position * (x.shape.volume(x.scale) / volume))
+            .fold(Vec3::default(), |a, b| a + b);
+        let mut inertia = [0.0f32; 9];
+        for child in &mut children {
+            child.position -= centre;
+            //each child's own inertia turned into the compound's frame, then moved out to where
+            //it sits by the parallel axis theorem, weighted by its share of the mass
+            let weight = child.shape.volume(child.scale) / volume;
+            let rotation = child.rotation.to_mat3();
+            let own = rotation * child.shape.unit_inertia(child.scale) * rotation.transpose();
+            let d = [child.position.x, child.position.y, child.position.z];
+            let distance_squared = child.position.dot(&child.position);
+            for i in 0..3 {
+                for j in 0..3 {
+                    let identity = if i == j { distance_squared } else { 0.0 };
+                    inertia[j * 3 + i] += weight * (own.array[j * 3 + i] + identity - d[i] * d[j]);
+                }
+            }
+        }
+        let corners = |child: &Child| {
+            let (min, max) = child.shape.bounds(child.scale);
+            std::array::from_fn::<_, 8, _>(|i| {
+                let corner = Vec3 {
+                    x: if i & 1 == 0 { min.x } else { max.x },
+                    y: if i & 2 == 0 { min.y } else { max.y },
+                    z: if i & 4 == 0 { min.z } else { max.z },
+                };
+                child.position + corner.rotate(child.rotation)
+            })
+        };
+        let bounds = AABB::around(children.iter().flat_map(corners));
+        let mut unit_inertia = Mat3::zero();
+        unit_inertia.array = inertia;
+        Some(Self {
+            min: bounds.min,
+            max: bounds.max,
+            volume,
+            unit_inertia,
+            bounding_radius: children
+                .iter()
+                .map(|x| x.position.mag() + x.shape.bounding_radius(x.scale))
+                .fold(0.0, f32::max),
+            thinnest: children
+                .iter()
+                .map(|x| {
+                    let extents = x.shape.extents(x.scale);
+                    extents.x.min(extents.y).min(extents.z)
+                })
+                .fold(f32::INFINITY, f32::min),
+            children,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compound_mass_properties() {
+        let unit = Vec3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        };
+        //two unit cubes side by side are a 2x1x1 box, wherever they're placed
+        let halves = Compound::new(
+            [2.0, 3.0]
+                .map(|x| Child {
+                    shape: Shape::Cuboid,
+                    scale: unit,
+                    position: Vec3 { x, y: 5.0, z: 0.0 },
+                    ..Default::default()
+                })
+                .to_vec(),
+        )
+        .unwrap();
+        let whole = Shape::Cuboid.unit_inertia(Vec3 {
+            x: 2.0,
+            y: 1.0,
+            z: 1.0,
+        });
+        assert!((halves.volume - 2.0).abs() < 1e-6);
+        assert!((halves.children[0].position.x + 0.5).abs() < 1e-6);
+        assert!(halves.children[1].position.y.abs() < 1e-6);
+        assert!(
+            halves
+                .unit_inertia
+                .array
+                .iter()
+                .zip(whole.array)
+                .all(|(a, b)| (a - b).abs() < 1e-6)
+        );
+        assert!((halves.min.x + 1.0).abs() < 1e-6 && (halves.max.z - 0.5).abs() < 1e-6);
+        assert_eq!(halves.thinnest, 1.0);
+
+        //a capsule lying along x beside a cube, an l of sorts. the centre of mass ends up nearer
+        //the heavier cube and the tensor stays symmetric
+        let l = Compound::new(vec![
+            Child {
+                shape: Shape::Cuboid,
+                scale: unit,
+                ..Default::default()
+            },
+            Child {
+                shape: Shape::Capsule {
+                    radius: 0.25,
+                    half_height: 1.0,
+                },
+                position: Vec3 {
+                    x: 1.5,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                rotation: Quaternion::from_angle(
+                    &Vec3 {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 1.0,
+                    },
+                    std::f32::consts::FRAC_PI_2,
+                ),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        let shift = l.children[0].position.x;
+        assert!(shift < 0.0 && shift > -0.75);
+        let array = l.unit_inertia.array;
+        assert!((array[1] - array[3]).abs() < 1e-6);
+        //the capsule lies along x, so it's hardest to spin about y and z
+        assert!(array[0] < array[4] && array[0] < array[8]);
+
+        assert!(Compound::new(Vec::new()).is_none());
+        assert!(
+            Compound::new(vec![Child {
+                shape: Shape::Compound(std::sync::Arc::new(halves)),
+                ..Default::default()
+            }])
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use crate::{
+    hull::{Triangle, horizon},
+    math::{EPSILON, Vec3},
+};
+
+const MAX_ITERATIONS: usize = 64; //each, for gjk and for epa
+
+#[derive(Debug, Clone, Copy)]
+pub enum Query {
+    Apart {
+        on_a: Vec3, //closest points
+        on_b: Vec3,
+    },
+    Overlapping {
+        normal: Vec3, //b->a, moving a depth along it separates them
+        depth: f32,
+        on_a: Vec3, //deepest points
+        on_b: Vec3,
+    },
+}
+
+//a point of the minkowski difference a - b, and the points of a and b it came from
+#[derive(Debug, Clone, Copy)]
+struct SupportPoint {
+    point: Vec3,
+    a: Vec3,
+    b: Vec3,
+}
+
+//gjk for how far apart two convex shapes are, then epa for how far they overlap if they do.
+//both shapes are support functions, the furthest point in a world space direction. start_dir is
+//a guess at b->a. once they're known to be more than max_distance apart it stops refining the
+//closest points. None if the shapes are too degenerate to tell
+pub fn query(
+    support_a: impl Fn(Vec3) -> Vec3,
+    support_b: impl Fn(Vec3) -> Vec3,
+    start_dir: Vec3,
+    max_distance: f32,
+) -> Option<Query> {
+    let support = |dir: Vec3| {
+        let (a, b) = (support_a(dir), support_b(-dir));
+        SupportPoint { point: a - b, a, b }
+    };
+    let mut simplex = vec![support(start_dir.normalize().unwrap_or(AXES[0]))];
+    let mut last: Option<Query> = None;
+    let mut last_distance = f32::INFINITY;
+    for _ in 0..MAX_ITERATIONS {
+        let weights = closest_on_simplex(&mut simplex);
+        let closest = weighted(&simplex, &weights, |x| x.point);
+        //the origin's inside, or as good as touching
+        if simplex.len() == 4 || closest.mag() < EPSILON {
+            return epa(simplex, &support);
+        }
+        //rounding can make it go round in circles instead of getting any closer, in which case
+        //the last answer is as good as it gets
+        if closest.mag() >= last_distance {
+            return last;
+        }
+        let apart = Query::Apart {
+            on_a: weighted(&simplex, &weights, |x| x.a),
+            on_b: weighted(&simplex, &weights, |x| x.b),
+        };
+        (last, last_distance) = (Some(apart), closest.mag());
+        let next = support(-closest);
+        //nothing on the difference is closer to the origin than what's already been found
+        let progress = closest.dot(&closest) - closest.dot(&next.point);
+        //or no point on it is within max_distance of the origin either
+        let lower_bound = closest.dot(&next.point) / closest.mag();
+        if progress <= EPSILON * closest.dot(&closest)
+            || lower_bound > max_distance
+            || simplex
+                .iter()
+                .any(|x| x.point.distance(&next.point) < EPSILON)
+        {
+            return last;
+        }
+        simplex.push(next);
+    }
+    None
+}
+
+const AXES: [Vec3; 3] = [
+    Vec3 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    },
+    Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    },
+    Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    },
+];
+
+fn weighted(simplex: &[SupportPoint], weights: &[f32], f: impl Fn(&SupportPoint) -> Vec3) -> Vec3 {
+    simplex
+        .iter()
+        .zip(weights)
+        .fold(Vec3::default(), |acc, (x, w)| acc + f(x) * *w)
+}
+
+//the closest point of the simplex to the origin lies inside exactly one of its faces, edges or
+//corners, and one that has the newest point since the rest were already closest last time. with
+//at most 4 points it's cheap enough to try all of those and keep the closest. the simplex is cut
+//down to just the points that make it up, and their barycentric weights are returned
+fn closest_on_simplex(simplex: &mut Vec<SupportPoint>) -> [f32; 4] {
+    let newest = 1 << (simplex.len() - 1);
+    let mut best = (f32::INFINITY, 0, [0.0; 4]);
+    for mask in (1..1u32 << simplex.len()).filter(|x| x & newest != 0) {
+        let mut points = [Vec3::default(); 4];
+        let mut len = 0;
+        for (i, x) in simplex.iter().enumerate() {
+            if mask & 1 << i != 0 {
+                points[len] = x.point;
+                len += 1;
+            }
+        }
+        let Some(weights) = affine_weights(&points[..len]) else {
+            continue;
+        };
+        if weights.iter().any(|&x| x < 0.0) {
+            continue;
+        }
+        let closest = points
+            .iter()
+            .zip(&weights)
+            .fold(Vec3::default(), |acc, (x, w)| acc + *x * *w);
+        let distance = closest.dot(&closest);
+        if distance < best.0 {
+            best = (distance, mask, weights);
+        }
+    }
+    //the newest point alone always has a weight of 1, so something's always found
+    let (_, mask, weights) = best;
+    let mut i = 0;
+    simplex.retain(|_| {
+        i += 1;
+        mask & 1 << (i - 1) != 0
+    });
+    weights
+}
+
+//barycentric weights of the point in the points' affine hull closest to the origin, None if the
+//points don't span as many dimensions as there are of them. weights past points.len() are 0.
+//ratios of lengths, areas or volumes rather than solving for them, which keeps thin simplices
+//against big shapes from losing all their precision
+fn affine_weights(points: &[Vec3]) -> Option<[f32; 4]> {
+    let mut weights = [0.0; 4];
+    match *points {
+        [_] => weights[0] = 1.0,
+        [a, b] => {
+            let ab = b - a;
+            if ab.mag() <= f32::EPSILON * a.mag().max(b.mag()) {
+                return None;
+            }
+            let t = -a.dot(&ab) / ab.dot(&ab);
+            weights[..2].copy_from_slice(&[1.0 - t, t]);
+        }
+        [a, b, c] => {
+            let normal = (b - a).cross(&(c - a));
+            if normal.mag() <= f32::EPSILON * (b - a).mag() * (c - a).mag() {
+                return None;
+            }
+            //the origin projected onto the plane, then the areas it splits the triangle into
+            let p = normal * (a.dot(&normal) / normal.dot(&normal));
+            let area =
+                |x: Vec3, y: Vec3| (x - p).cross(&(y - p)).dot(&normal) / normal.dot(&normal);
+            let (u, v) = (area(b, c), area(c, a));
+            weights[..3].copy_from_slice(&[u, v, 1.0 - u - v]);
+        }
+        [a, b, c, d] => {
+            let volume = |p: [Vec3; 4]| (p[1] - p[0]).dot(&(p[2] - p[0]).cross(&(p[3] - p[0])));
+            let total = volume([a, b, c, d]);
+            let scale = (b - a).mag() * (c - a).mag() * (d - a).mag();
+            if total.abs() <= f32::EPSILON * scale {
+                return None;
+            }
+            //the origin standing in for each corner in turn
+            for (i, weight) in weights.iter_mut().enumerate() {
+                let mut corners = [a, b, c, d];
+                corners[i] = Vec3::default();
+                *weight = volume(corners) / total;
+            }
+        }
+        _ => return None,
+    }
+    Some(weights)
+}
+
+//expanding polytope: push out the face of the difference nearest the origin until it's on the
+//surface, that face is the shortest way out
+fn epa(mut simplex: Vec<SupportPoint>, support: &impl Fn(Vec3) -> SupportPoint) -> Option<Query> {
+    blow_up(&mut simplex, support)?;
+    let mut points: Vec<Vec3> = simplex.iter().map(|x| x.point).collect();
+    let centre = points.iter().fold(Vec3::default(), |acc, x| acc + *x) / 4.0;
+    let mut triangles = Vec::with_capacity(4);
+    for [i, j, k] in [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]] {
+        let triangle = Triangle::new(&points, [i, j, k])?;
+        triangles.push(if triangle.distance_to(&centre) > 0.0 {
+            Triangle::new(&points, [i, k, j])?
+        } else {
+            triangle
+        });
+    }
+    for _ in 0..MAX_ITERATIONS {
+        let nearest = triangles
+            .iter()
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))?
+            .clone();
+        let next = support(nearest.normal);
+        if next.point.dot(&nearest.normal) - nearest.distance <= EPSILON {
+            return Some(overlap(&simplex, &points, &nearest));
+        }
+        let apex = points.len();
+        points.push(next.point);
+        simplex.push(next);
+        let visible: Vec<bool> = triangles
+            .iter()
+            .map(|x| x.distance_to(&next.point) > 0.0)
+            .collect();
+        let edges = horizon(&triangles, &visible);
+        let mut kept: Vec<Triangle> = triangles
+            .into_iter()
+            .zip(visible)
+            .filter(|x| !x.1)
+            .map(|x| x.0)
+            .collect();
+        kept.extend(
+            edges
+                .into_iter()
+                .filter_map(|(a, b)| Triangle::new(&points, [a, b, apex])),
+        );
+        triangles = kept;
+    }
+    let nearest = triangles
+        .iter()
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))?;
+    Some(overlap(&simplex, &points, nearest))
+}
+
+fn overlap(simplex: &[SupportPoint], points: &[Vec3], nearest: &Triangle) -> Query {
+    //where the origin projects onto the face, as weights of its corners
+    let projection = nearest.normal * nearest.distance;
+    let corners = nearest.vertices.map(|i| points[i] - projection);
+    let weights = affine_weights(&corners).unwrap_or([1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0, 0.0]);
+    let corners = nearest.vertices.map(|i| simplex[i]);
+    Query::Overlapping {
+        normal: -nearest.normal,
+        depth: nearest.distance,
+        on_a: weighted(&corners, &weights, |x| x.a),
+        on_b: weighted(&corners, &weights, |x| x.b),
+    }
+}
+
+//gjk stops as soon as it finds the origin, so touching or shallow overlaps can leave it with
+//less than a tetrahedron. add points in new directions until it has one
+fn blow_up(simplex: &mut Vec<SupportPoint>, support: &impl Fn(Vec3) -> SupportPoint) -> Option<()> {
+    while simplex.len() < 4 {
+        let points: Vec<Vec3> = simplex.iter().map(|x| x.point).collect();
+        //directions to search in, and how far a point has to be from the current ones to count
+        let (directions, off): (Vec<Vec3>, Box<dyn Fn(Vec3) -> f32>) = match points.len() {
+            1 => (AXES.to_vec(), Box::new(|x| x.distance(&points[0]))),
+            2 => {
+                let line = (points[1] - points[0]).normalize()?;
+                (
+                    AXES.iter()
+                        .filter_map(|x| line.cross(x).normalize())
+                        .collect(),
+                    Box::new(move |x| (x - points[0]).cross(&line).mag()),
+                )
+            }
+            _ => {
+                let normal = (points[1] - points[0])
+                    .cross(&(points[2] - points[0]))
+                    .normalize()?;
+                (
+                    vec![normal],
+                    Box::new(move |x| (x - points[0]).dot(&normal).abs()),
+                )
+            }
+        };
+        let next = directions
+            .iter()
+            .flat_map(|x| [support(*x), support(-*x)])
+            .find(|x| off(x.point) > EPSILON)?;
+        simplex.push(next);
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //unit cube centred on centre
+    fn cube(centre: Vec3) -> impl Fn(Vec3) -> Vec3 {
+        move |dir: Vec3| {
+            let half = |x: f32| if x >= 0.0 { 0.5 } else { -0.5 };
+            centre
+                + Vec3 {
+                    x: half(dir.x),
+                    y: half(dir.y),
+                    z: half(dir.z),
+                }
+        }
+    }
+
+    #[test]
+    fn test_gjk_distance() {
+        let b = Vec3 {
+            x: 3.0,
+            y: 0.5,
+            z: 0.0,
+        };
+        let Some(Query::Apart { on_a, on_b }) =
+            query(cube(Vec3::default()), cube(b), -b, f32::INFINITY)
+        else {
+            panic!("should be apart");
+        };
+        assert!(((on_b - on_a).x - 2.0).abs() < EPSILON);
+        assert!((on_b - on_a).y.abs() < EPSILON);
+        assert!(((on_b - on_a).mag() - 2.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_epa_depth() {
+        let b = Vec3 {
+            x: 0.1,
+            y: -0.8,
+            z: 0.2,
+        };
+        for start in [-b, Vec3::default()] {
+            let Some(Query::Overlapping {
+                normal,
+                depth,
+                on_a,
+                on_b,
+            }) = query(cube(Vec3::default()), cube(b), start, f32::INFINITY)
+            else {
+                panic!("should overlap");
+            };
+            //out through the top of b
+            assert!((normal.y - 1.0).abs() < EPSILON);
+            assert!((depth - 0.2).abs() < EPSILON);
+            assert!(((on_b - on_a).y - 0.2).abs() < EPSILON);
+        }
+    }
+}
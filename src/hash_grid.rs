@@ -1,4 +1,7 @@
-use crate::{math::Vec3, world::Cuboid};
+use crate::{
+    math::Vec3,
+    world::{AABB, Cuboid},
+};
 
 const BUCKET_CAPACITY: usize = 8;
 #[derive(Debug)]
@@ -10,41 +13,70 @@ pub struct HashGrid {
 }
 
 impl HashGrid {
-    pub fn new(instances: &[Cuboid], n: usize) -> Self {
+    pub fn new(instances: &[Cuboid]) -> Self {
+        let n = instances.len().max(1); //hash() is modulo n
         let mut buckets = Vec::with_capacity(n);
         for _ in 0..n {
             buckets.push(Vec::with_capacity(BUCKET_CAPACITY));
         }
-        let mut spacing = Vec3::default();
-        for i in 0..n {
-            spacing = Vec3 {
-                x: spacing.x.max(instances[i].scale.x),
-                y: spacing.y.max(instances[i].scale.y),
-                z: spacing.z.max(instances[i].scale.z),
-            }
-        }
-        spacing *= 1.5;
-        Self {
-            spacing,
+        let mut grid = Self {
+            spacing: Vec3::default(),
             buckets,
             n,
+        };
+        for instance in instances {
+            grid.fit(instance);
+        }
+        grid
+    }
+    //grows spacing so instance still spans at most 2 cells per axis. static shapes never go in
+    pub fn fit(&mut self, instance: &Cuboid) {
+        if instance.shape.is_static() {
+            return;
         }
+        let scale = instance.extents() * 1.5;
+        self.spacing = Vec3 {
+            x: self.spacing.x.max(scale.x),
+            y: self.spacing.y.max(scale.y),
+            z: self.spacing.z.max(scale.z),
+        };
     }
-    pub fn init(&mut self, instances: &[Cuboid]) {
-        let mut largest: usize = 0;
-        for i in 0..self.n {
-            let instance = &instances[i];
-            Self::for_each_cell(instance, self.spacing, self.n, |index| {
-                self.buckets[index].push(instance.index);
-            });
-            largest = largest.max(self.buckets[i].len());
+    //only ever grows, buckets are rebuilt every tick anyway
+    pub fn reserve(&mut self, n: usize) {
+        if n <= self.n {
+            return;
         }
+        self.buckets
+            .resize_with(n, || Vec::with_capacity(BUCKET_CAPACITY));
+        self.n = n;
+    }
+    //aabbs can be bigger than the instance itself, e.g. fattened by how far it moves per tick
+    pub fn init(&mut self, instances: impl IntoIterator<Item = (usize, AABB)>) {
+        for (index, aabb) in instances {
+            self.insert(index, &aabb);
+        }
+    }
+    //inserting the same instance twice is harmless, pairs with itself are skipped
+    pub fn insert(&mut self, index: usize, aabb: &AABB) {
+        Self::for_each_cell(aabb, self.spacing, self.n, |cell| {
+            self.buckets[cell].push(index);
+        });
+    }
+    //every instance sharing a cell with aabb, possibly more than once
+    pub fn query<F>(&self, aabb: &AABB, mut f: F)
+    where
+        F: FnMut(usize),
+    {
+        Self::for_each_cell(aabb, self.spacing, self.n, |index| {
+            for &instance_index in &self.buckets[index] {
+                f(instance_index);
+            }
+        });
     }
-    pub fn for_each_cell<F>(instance: &Cuboid, spacing: Vec3, n: usize, mut f: F)
+    pub fn for_each_cell<F>(aabb: &AABB, spacing: Vec3, n: usize, mut f: F)
     where
         F: FnMut(usize),
     {
-        let aabb = instance.aabb;
         let Vec3 {
             x: min_x_float,
             y: min_y_float,
@@ -71,7 +103,10 @@ impl HashGrid {
         }
     }
     fn hash(cell: (i32, i32, i32), n: usize) -> usize {
-        (((cell.0 * 73856093) ^ (cell.1 * 19349663) ^ (cell.2 * 83492791)) //https://matthias-research.github.io/pages/publications/tetraederCollision.pdf
+        //wraps on purpose, cells only ~26 away from the origin already overflow
+        ((cell.0.wrapping_mul(73856093)
+            ^ cell.1.wrapping_mul(19349663)
+            ^ cell.2.wrapping_mul(83492791)) //https://matthias-research.github.io/pages/publications/tetraederCollision.pdf
             as usize)
             % n
     }
@@ -87,7 +122,6 @@ mod tests {
     use super::*;
     #[test]
     fn test_cell_iterator() {
-        let n = 2;
         let mut instances = vec![
             Cuboid {
                 index: 0,
@@ -112,10 +146,10 @@ mod tests {
         for x in &mut instances {
             x.update_derived();
         }
-        let mut grid = HashGrid::new(&instances, n);
-        grid.init(&instances);
+        let mut grid = HashGrid::new(&instances);
+        grid.init(instances.iter().map(|x| (x.index, x.aabb)));
         let mut len = 0;
-        HashGrid::for_each_cell(&instances[0], grid.spacing, grid.n, |cell_index| {
+        HashGrid::for_each_cell(&instances[0].aabb, grid.spacing, grid.n, |cell_index| {
             let bucket = &grid.buckets[cell_index];
             dbg!(&bucket);
             assert_eq!(bucket.len(), 2);
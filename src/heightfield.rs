@@ -0,0 +1,185 @@
+use crate::{math::Vec3, trimesh::edge_limit, world::AABB};
+
+//static terrain in its body's local space, a grid of heights centred on the origin in x and z.
+//each cell is two triangles split along its diagonal, one sided like a mesh's and facing up
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightfield {
+    pub heights: Vec<f32>, //x major, heights[i * rows + j] is column i, row j
+    pub columns: usize,    //samples along x
+    pub rows: usize,       //along z
+    pub cell_size: f32,
+    pub normals: Vec<Vec3>,         //one per triangle, two per cell
+    pub edge_limits: Vec<[f32; 3]>, //as for TriMesh
+    pub min: Vec3,                  //local bounding box
+    pub max: Vec3,
+    pub bounding_radius: f32,
+}
+
+impl Heightfield {
+    //None unless there's at least one cell and a height for every sample
+    pub fn new(heights: Vec<f32>, columns: usize, rows: usize, cell_size: f32) -> Option<Self> {
+        if columns < 2 || rows < 2 || heights.len() != columns * rows || cell_size <= 0.0 {
+            return None;
+        }
+        let mut field = Self {
+            heights,
+            columns,
+            rows,
+            cell_size,
+            normals: Vec::new(),
+            edge_limits: Vec::new(),
+            min: Vec3::default(),
+            max: Vec3::default(),
+            bounding_radius: 0.0,
+        };
+        field.normals = (0..field.triangle_count())
+            .map(|t| {
+                let [a, b, c] = field.corners(t);
+                (b - a).cross(&(c - a)).normalize().unwrap()
+            })
+            .collect();
+        field.edge_limits = (0..field.triangle_count())
+            .map(|t| {
+                let corners = field.corners(t);
+                std::array::from_fn(|k| match field.neighbour(t, k) {
+                    Some(other) => edge_limit(
+                        field.normals[t],
+                        field.normals[other],
+                        corners[k],
+                        corners[(k + 1) % 3],
+                    ),
+                    None => f32::INFINITY,
+                })
+            })
+            .collect();
+        let samples = (0..columns).flat_map(|i| (0..rows).map(move |j| (i, j)));
+        let bounds = AABB::around(samples.clone().map(|(i, j)| field.vertex(i, j)));
+        field.min = bounds.min;
+        field.max = bounds.max;
+        field.bounding_radius = samples
+            .map(|(i, j)| field.vertex(i, j).mag())
+            .fold(0.0, f32::max);
+        Some(field)
+    }
+    pub fn triangle_count(&self) -> usize {
+        (self.columns - 1) * (self.rows - 1) * 2
+    }
+    //every triangle whose bounding box touches aabb, which is in the field's local space. only the
+    //cells under it are looked at
+    pub fn query(&self, aabb: &AABB, mut f: impl FnMut(usize)) {
+        let cells = |min: f32, max: f32, samples: usize| {
+            let offset = (samples - 1) as f32 / 2.0;
+            let first = (min / self.cell_size + offset).floor().max(0.0) as usize;
+            let last = ((max / self.cell_size + offset).ceil().max(0.0) as usize).min(samples - 1);
+            first..last
+        };
+        for i in cells(aabb.min.x, aabb.max.x, self.columns) {
+            for j in cells(aabb.min.z, aabb.max.z, self.rows) {
+                for half in 0..2 {
+                    let t = self.triangle(i, j, half);
+                    if AABB::around(self.corners(t)).intersects(aabb) {
+                        f(t);
+                    }
+                }
+            }
+        }
+    }
+    pub fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        Vec3 {
+            x: (i as f32 - (self.columns - 1) as f32 / 2.0) * self.cell_size,
+            y: self.heights[i * self.rows + j],
+            z: (j as f32 - (self.rows - 1) as f32 / 2.0) * self.cell_size,
+        }
+    }
+    pub fn corners(&self, triangle: usize) -> [Vec3; 3] {
+        self.samples(triangle).map(|(i, j)| self.vertex(i, j))
+    }
+    //(column, row) of each corner, ccw from above. the diagonal is corners 2->0 of the first half
+    //and 0->1 of the second
+    pub fn samples(&self, triangle: usize) -> [(usize, usize); 3] {
+        let (i, j) = self.cell(triangle);
+        if triangle.is_multiple_of(2) {
+            [(i, j), (i, j + 1), (i + 1, j + 1)]
+        } else {
+            [(i, j), (i + 1, j + 1), (i + 1, j)]
+        }
+    }
+    fn cell(&self, triangle: usize) -> (usize, usize) {
+        let cell = triangle / 2;
+        (cell / (self.rows - 1), cell % (self.rows - 1))
+    }
+    fn triangle(&self, i: usize, j: usize, half: usize) -> usize {
+        (i * (self.rows - 1) + j) * 2 + half
+    }
+    //the triangle across edge k (corner k -> k + 1), None on the boundary
+    fn neighbour(&self, triangle: usize, k: usize) -> Option<usize> {
+        let (i, j) = self.cell(triangle);
+        match (triangle % 2, k) {
+            (0, 0) => (i > 0).then(|| self.triangle(i - 1, j, 1)),
+            (0, 1) => (j + 2 < self.rows).then(|| self.triangle(i, j + 1, 1)),
+            (1, 1) => (i + 2 < self.columns).then(|| self.triangle(i + 1, j, 0)),
+            (1, 2) => (j > 0).then(|| self.triangle(i, j - 1, 0)),
+            _ => Some(triangle ^ 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::EPSILON;
+
+    #[test]
+    fn test_heightfield_query_and_edges() {
+        //a ramp rising along x, flat across z
+        let (columns, rows) = (5, 4);
+        let heights = (0..columns)
+            .flat_map(|i| (0..rows).map(move |_| i as f32 * 0.5))
+            .collect();
+        let field = Heightfield::new(heights, columns, rows, 1.0).unwrap();
+        assert_eq!(field.triangle_count(), 24);
+        assert!((field.min.x + 2.0).abs() < EPSILON && (field.max.z - 1.5).abs() < EPSILON);
+        for t in 0..field.triangle_count() {
+            let normal = field.normals[t];
+            assert!(normal.y > 0.0 && normal.x < 0.0 && normal.z.abs() < EPSILON);
+            //every neighbour is coplanar, so nothing can lean over the inside edges
+            for k in 0..3 {
+                let expected = if field.neighbour(t, k).is_some() {
+                    0.0
+                } else {
+                    f32::INFINITY
+                };
+                assert_eq!(field.edge_limits[t][k], expected);
+            }
+        }
+
+        //a box in the middle of the field straddles the two cells either side of x = 0
+        let aabb = AABB::new(
+            Vec3 {
+                x: -0.2,
+                y: -1.0,
+                z: -0.3,
+            },
+            Vec3 {
+                x: 0.2,
+                y: 5.0,
+                z: 0.1,
+            },
+        );
+        let mut found = Vec::new();
+        field.query(&aabb, |x| found.push(x));
+        found.sort();
+        let expected: Vec<usize> = (0..field.triangle_count())
+            .filter(|&t| AABB::around(field.corners(t)).intersects(&aabb))
+            .collect();
+        assert_eq!(found, expected);
+        assert_eq!(found.len(), 4);
+        //and one off the edge finds nothing
+        let off_edge = aabb.translate(Vec3 {
+            x: 10.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        field.query(&off_edge, |_| panic!());
+    }
+}
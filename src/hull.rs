@@ -0,0 +1,428 @@
+use crate::math::{EPSILON, Mat3, Vec3};
+
+//a convex polyhedron in its body's local space
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexHull {
+    pub vertices: Vec<Vec3>,    //centred on the centre of mass
+    pub faces: Vec<Vec<usize>>, //into vertices, ccw seen from outside
+    pub normals: Vec<Vec3>,     //outward, one per face
+    pub min: Vec3,              //local bounding box
+    pub max: Vec3,
+    pub volume: f32,
+    pub unit_inertia: Mat3, //about the centre of mass, for a mass of 1
+    pub bounding_radius: f32,
+}
+
+//a triangle of a polytope that's still being built, shared with epa
+#[derive(Debug, Clone)]
+pub(crate) struct Triangle {
+    pub vertices: [usize; 3], //ccw seen from outside
+    pub normal: Vec3,
+    pub distance: f32, //of the plane from the origin, along normal
+}
+
+impl Triangle {
+    pub fn new(points: &[Vec3], vertices: [usize; 3]) -> Option<Self> {
+        let [a, b, c] = vertices.map(|i| points[i]);
+        let normal = (b - a).cross(&(c - a)).normalize()?;
+        Some(Self {
+            vertices,
+            normal,
+            distance: normal.dot(&a),
+        })
+    }
+    pub fn distance_to(&self, point: &Vec3) -> f32 {
+        self.normal.dot(point) - self.distance
+    }
+}
+
+//the outline of the visible triangles, as edges wound the same way as the triangles they came
+//from. joining each edge to the new point keeps the polytope closed and facing out
+pub(crate) fn horizon(triangles: &[Triangle], visible: &[bool]) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    for triangle in triangles.iter().zip(visible).filter(|x| *x.1).map(|x| x.0) {
+        let [a, b, c] = triangle.vertices;
+        edges.extend([(a, b), (b, c), (c, a)]);
+    }
+    //an edge shared by two visible triangles shows up once each way round
+    edges
+        .iter()
+        .filter(|(a, b)| !edges.contains(&(*b, *a)))
+        .copied()
+        .collect()
+}
+
+impl ConvexHull {
+    //quickhull. the points are moved so the centre of mass ends up at the origin, so place the
+    //body where the centre of mass should go. None if they're all in one plane
+    pub fn new(points: &[Vec3]) -> Option<Self> {
+        let scale = points
+            .iter()
+            .map(|x| x.x.abs().max(x.y.abs()).max(x.z.abs()))
+            .fold(0.0, f32::max);
+        let tolerance = EPSILON * scale.max(1.0);
+        let mut triangles = initial_tetrahedron(points, tolerance)?;
+        let mut outside: Vec<Vec<usize>> = vec![Vec::new(); triangles.len()];
+        let used: Vec<usize> = triangles.iter().flat_map(|x| x.vertices).collect();
+        let remaining: Vec<usize> = (0..points.len()).filter(|x| !used.contains(x)).collect();
+        assign(points, &triangles, &mut outside, remaining, tolerance);
+
+        //grow towards the furthest outside point of any triangle until none are left
+        while let Some(i) = outside.iter().position(|x| !x.is_empty()) {
+            let triangle = &triangles[i];
+            let apex = *outside[i]
+                .iter()
+                .max_by(|a, b| {
+                    triangle
+                        .distance_to(&points[**a])
+                        .total_cmp(&triangle.distance_to(&points[**b]))
+                })
+                .unwrap();
+            let visible: Vec<bool> = triangles
+                .iter()
+                .map(|x| x.distance_to(&points[apex]) > tolerance)
+                .collect();
+            let edges = horizon(&triangles, &visible);
+            let mut orphans = Vec::new();
+            let mut kept_triangles = Vec::with_capacity(triangles.len());
+            let mut kept_outside = Vec::with_capacity(triangles.len());
+            for ((triangle, points), visible) in triangles.into_iter().zip(outside).zip(visible) {
+                if visible {
+                    orphans.extend(points.into_iter().filter(|&x| x != apex));
+                } else {
+                    kept_triangles.push(triangle);
+                    kept_outside.push(points);
+                }
+            }
+            let first_new = kept_triangles.len();
+            for (a, b) in edges {
+                if let Some(triangle) = Triangle::new(points, [a, b, apex]) {
+                    kept_triangles.push(triangle);
+                    kept_outside.push(Vec::new());
+                }
+            }
+            (triangles, outside) = (kept_triangles, kept_outside);
+            //only the new triangles can see the orphans, the rest already couldn't
+            let (old, new) = triangles.split_at(first_new);
+            let (_, new_outside) = outside.split_at_mut(old.len());
+            assign(points, new, new_outside, orphans, tolerance);
+        }
+        Some(Self::from_triangles(points, &triangles))
+    }
+
+    fn from_triangles(points: &[Vec3], triangles: &[Triangle]) -> Self {
+        //tetrahedra from the origin to each triangle, see "how to find the inertia tensor (or
+        //other mass properties) of a 3d solid body represented by a triangle mesh", blow & binstock
+        let mut volume = 0.0;
+        let mut centre = Vec3::default();
+        let mut covariance = [[0.0f32; 3]; 3];
+        for triangle in triangles {
+            let [a, b, c] = triangle.vertices.map(|i| points[i]);
+            let det = a.dot(&b.cross(&c));
+            volume += det / 6.0;
+            centre += (a + b + c) * (det / 24.0);
+            let [sum, a, b, c] = [a + b + c, a, b, c].map(|v| [v.x, v.y, v.z]);
+            for (i, row) in covariance.iter_mut().enumerate() {
+                for (j, entry) in row.iter_mut().enumerate() {
+                    let products = sum[i] * sum[j] + a[i] * a[j] + b[i] * b[j] + c[i] * c[j];
+                    *entry += det / 120.0 * products;
+                }
+            }
+        }
+        let centre = centre / volume;
+        //parallel axis theorem, moved to the centre of mass
+        let centre_array = [centre.x, centre.y, centre.z];
+        for (i, row) in covariance.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry -= volume * centre_array[i] * centre_array[j];
+            }
+        }
+        let trace = covariance[0][0] + covariance[1][1] + covariance[2][2];
+        let mut inertia = Mat3::zero();
+        for (i, row) in covariance.iter().enumerate() {
+            for (j, entry) in row.iter().enumerate() {
+                let identity = if i == j { trace } else { 0.0 };
+                inertia.array[j * 3 + i] = (identity - entry) / volume;
+            }
+        }
+
+        let mut faces = merge_coplanar(points, triangles);
+        //only keep what the faces use, numbered in order
+        let mut remap = vec![usize::MAX; points.len()];
+        let mut vertices = Vec::new();
+        for face in &mut faces.0 {
+            for i in face.iter_mut() {
+                if remap[*i] == usize::MAX {
+                    remap[*i] = vertices.len();
+                    vertices.push(points[*i] - centre);
+                }
+                *i = remap[*i];
+            }
+        }
+        let mut min = Vec3 {
+            x: f32::INFINITY,
+            y: f32::INFINITY,
+            z: f32::INFINITY,
+        };
+        let mut max = -min;
+        for v in &vertices {
+            min = Vec3 {
+                x: min.x.min(v.x),
+                y: min.y.min(v.y),
+                z: min.z.min(v.z),
+            };
+            max = Vec3 {
+                x: max.x.max(v.x),
+                y: max.y.max(v.y),
+                z: max.z.max(v.z),
+            };
+        }
+        Self {
+            bounding_radius: vertices.iter().map(Vec3::mag).fold(0.0, f32::max),
+            vertices,
+            faces: faces.0,
+            normals: faces.1,
+            min,
+            max,
+            volume,
+            unit_inertia: inertia,
+        }
+    }
+    //both sides of one triangle, so mesh contacts can go through the same narrow phase as hulls.
+    //it has no volume, only frozen bodies should be made of it. corners are ccw seen from the
+    //front, and centred on the origin like any other hull's. None if they're in a line
+    pub(crate) fn flat(corners: [Vec3; 3]) -> Option<Self> {
+        let mut hull = Self {
+            vertices: vec![Vec3::default(); 3],
+            faces: vec![vec![0, 1, 2], vec![0, 2, 1]],
+            normals: vec![Vec3::default(); 2],
+            min: Vec3::default(),
+            max: Vec3::default(),
+            volume: 0.0,
+            unit_inertia: Mat3::zero(),
+            bounding_radius: 0.0,
+        };
+        hull.reshape_flat(corners)?;
+        Some(hull)
+    }
+    //moves a hull made by flat() onto another triangle without reallocating. None, leaving it
+    //as it was, if they're in a line
+    pub(crate) fn reshape_flat(&mut self, corners: [Vec3; 3]) -> Option<()> {
+        let [a, b, c] = corners;
+        let normal = (b - a).cross(&(c - a)).normalize()?;
+        let fold = |f: fn(f32, f32) -> f32| Vec3 {
+            x: f(f(a.x, b.x), c.x),
+            y: f(f(a.y, b.y), c.y),
+            z: f(f(a.z, b.z), c.z),
+        };
+        self.vertices.copy_from_slice(&corners);
+        self.normals.copy_from_slice(&[normal, -normal]);
+        self.min = fold(f32::min);
+        self.max = fold(f32::max);
+        self.bounding_radius = corners.iter().map(Vec3::mag).fold(0.0, f32::max);
+        Some(())
+    }
+    //the vertex furthest along dir, local space
+    pub fn support(&self, dir: &Vec3) -> Vec3 {
+        *self
+            .vertices
+            .iter()
+            .max_by(|a, b| a.dot(dir).total_cmp(&b.dot(dir)))
+            .unwrap()
+    }
+}
+
+//the biggest tetrahedron it can find quickly, wound so every normal points out
+fn initial_tetrahedron(points: &[Vec3], tolerance: f32) -> Option<Vec<Triangle>> {
+    if points.len() < 4 {
+        return None;
+    }
+    let furthest = |score: &dyn Fn(&Vec3) -> f32| {
+        (0..points.len())
+            .max_by(|&a, &b| score(&points[a]).total_cmp(&score(&points[b])))
+            .unwrap()
+    };
+    //the pair of extreme points along whichever axis they're furthest apart on
+    let mut best = (0, 0, f32::NEG_INFINITY);
+    for axis in [
+        Vec3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        },
+    ] {
+        let (a, b) = (furthest(&|x| -x.dot(&axis)), furthest(&|x| x.dot(&axis)));
+        let spread = (points[b] - points[a]).dot(&axis);
+        if spread > best.2 {
+            best = (a, b, spread);
+        }
+    }
+    let (a, b, _) = best;
+    let line = (points[b] - points[a]).normalize()?;
+    let c = furthest(&|x| {
+        let offset = *x - points[a];
+        (offset - line * offset.dot(&line)).mag()
+    });
+    let plane = Triangle::new(points, [a, b, c])?;
+    let d = furthest(&|x| plane.distance_to(x).abs());
+    if plane.distance_to(&points[d]).abs() <= tolerance {
+        return None;
+    }
+    let centre = (points[a] + points[b] + points[c] + points[d]) / 4.0;
+    let mut triangles = Vec::with_capacity(4);
+    for [i, j, k] in [[a, b, c], [a, b, d], [a, c, d], [b, c, d]] {
+        let triangle = Triangle::new(points, [i, j, k])?;
+        triangles.push(if triangle.distance_to(&centre) > 0.0 {
+            Triangle::new(points, [i, k, j])?
+        } else {
+            triangle
+        });
+    }
+    Some(triangles)
+}
+
+//each point goes to the first triangle it's outside of, points inside all of them are dropped
+fn assign(
+    points: &[Vec3],
+    triangles: &[Triangle],
+    outside: &mut [Vec<usize>],
+    candidates: Vec<usize>,
+    tolerance: f32,
+) {
+    for point in candidates {
+        if let Some(i) = triangles
+            .iter()
+            .position(|x| x.distance_to(&points[point]) > tolerance)
+        {
+            outside[i].push(point);
+        }
+    }
+}
+
+//triangles sharing a plane become one polygon, so a box comes out with 6 quads. on a convex
+//hull each normal only occurs once, so grouping by normal is enough
+fn merge_coplanar(points: &[Vec3], triangles: &[Triangle]) -> (Vec<Vec<usize>>, Vec<Vec3>) {
+    let mut faces: Vec<Vec<usize>> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    for triangle in triangles {
+        let face = match normals
+            .iter()
+            .position(|x| x.dot(&triangle.normal) > 1.0 - EPSILON)
+        {
+            Some(i) => i,
+            None => {
+                normals.push(triangle.normal);
+                faces.push(Vec::new());
+                faces.len() - 1
+            }
+        };
+        for i in triangle.vertices {
+            if !faces[face].contains(&i) {
+                faces[face].push(i);
+            }
+        }
+    }
+    for (face, normal) in faces.iter_mut().zip(&normals) {
+        let centre =
+            face.iter().fold(Vec3::default(), |acc, &i| acc + points[i]) / face.len() as f32;
+        let u = (points[face[0]] - centre).normalize().unwrap();
+        let w = normal.cross(&u);
+        let angle = |i: &usize| {
+            let v = points[*i] - centre;
+            v.dot(&w).atan2(v.dot(&u))
+        };
+        face.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+        //points partway along an edge don't change the outline
+        let mut i = 0;
+        while face.len() > 3 && i < face.len() {
+            let n = face.len();
+            let [prev, cur, next] = [face[(i + n - 1) % n], face[i], face[(i + 1) % n]];
+            let turn = (points[cur] - points[prev]).cross(&(points[next] - points[cur]));
+            if turn.dot(normal) <= EPSILON * EPSILON {
+                face.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+    (faces, normals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube_points() -> Vec<Vec3> {
+        let mut points = Vec::new();
+        for x in [-0.5, 0.5] {
+            for y in [-0.5, 0.5] {
+                for z in [-0.5, 0.5] {
+                    points.push(Vec3 { x, y, z });
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn test_hull_of_cube() {
+        let mut points = cube_points();
+        //interior points and ones partway along an edge don't end up as vertices
+        points.push(Vec3::default());
+        points.push(Vec3 {
+            x: 0.1,
+            y: -0.2,
+            z: 0.3,
+        });
+        points.push(Vec3 {
+            x: 0.0,
+            y: 0.5,
+            z: 0.5,
+        });
+        //moved so the centre of mass is off the origin
+        for point in &mut points {
+            *point += Vec3 {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            };
+        }
+        let hull = ConvexHull::new(&points).unwrap();
+        assert_eq!(hull.vertices.len(), 8);
+        assert_eq!(hull.faces.len(), 6);
+        assert!(hull.faces.iter().all(|x| x.len() == 4));
+        assert!((hull.volume - 1.0).abs() < 1e-4);
+        for v in &hull.vertices {
+            assert!((v.x.abs() - 0.5).abs() < 1e-4);
+        }
+        //same as a unit cuboid's, (1/12)(1+1)
+        for i in 0..3 {
+            for j in 0..3 {
+                let expected = if i == j { 1.0 / 6.0 } else { 0.0 };
+                assert!((hull.unit_inertia.array[j * 3 + i] - expected).abs() < 1e-4);
+            }
+        }
+        //faces are wound ccw from outside
+        for (face, normal) in hull.faces.iter().zip(&hull.normals) {
+            let [a, b, c] = [0, 1, 2].map(|i| hull.vertices[face[i]]);
+            assert!((b - a).cross(&(c - b)).dot(normal) > 0.0);
+            assert!(a.dot(normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_hull_rejects_flat_points() {
+        let points: Vec<Vec3> = cube_points().into_iter().filter(|x| x.y > 0.0).collect();
+        assert!(ConvexHull::new(&points).is_none());
+    }
+}
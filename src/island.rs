@@ -0,0 +1,366 @@
+use std::thread;
+
+use crate::{
+    arena::Arena,
+    joint::{Joint, WORLD_SLOT, world_anchor},
+    math::Vec3,
+    physics::{CollisionInfo, IslandContact, IslandJoint, resolve_collisions},
+    world::{Cuboid, WorldSettings},
+};
+
+//a group of awake bodies connected through contacts and joints, solved independently of every
+//other island
+#[derive(Debug, Default)]
+pub struct Island {
+    //slots, the first awake_count are awake and the rest are static, WORLD_SLOT included
+    pub bodies: Vec<usize>,
+    pub awake_count: usize,
+    pub collisions: Vec<usize>,           //into the tick's collision list
+    pub pairs: Vec<(usize, usize)>,       //local indices into bodies, parallel to collisions
+    pub joints: Vec<usize>,               //into build_islands()' joint_bodies
+    pub joint_pairs: Vec<(usize, usize)>, //likewise parallel to joints
+    pub iterations: usize,
+}
+
+impl Island {
+    //whether there's anything to solve
+    pub fn is_connected(&self) -> bool {
+        !self.collisions.is_empty() || !self.joints.is_empty()
+    }
+}
+
+//every awake body ends up in exactly one island, including ones with no contacts. joint_bodies
+//is the slots each joint joins, WORLD_SLOT for the world
+pub fn build_islands(
+    collisions: &[CollisionInfo],
+    joint_bodies: &[(usize, usize)],
+    instances: &Arena<Cuboid>,
+    settings: &WorldSettings,
+) -> Vec<Island> {
+    let slot_count = instances.slot_count();
+    let is_awake =
+        |index: usize| index != WORLD_SLOT && instances.get_by_index(index).unwrap().is_awake();
+    let links = || {
+        collisions
+            .iter()
+            .map(|x| (x.instance_index, x.other_index))
+            .chain(joint_bodies.iter().copied())
+    };
+    let mut union_find = UnionFind::new(slot_count);
+    for (a, b) in links() {
+        //static bodies don't join islands, otherwise everything on the floor is one island
+        if is_awake(a) && is_awake(b) {
+            union_find.union(a, b);
+        }
+    }
+
+    let mut island_of_root = vec![usize::MAX; slot_count];
+    let mut local_index = vec![usize::MAX; slot_count];
+    let mut islands: Vec<Island> = Vec::new();
+    for instance in instances.iter() {
+        if !instance.is_awake() {
+            continue;
+        }
+        let root = union_find.find(instance.index);
+        if island_of_root[root] == usize::MAX {
+            island_of_root[root] = islands.len();
+            islands.push(Island::default());
+        }
+        let island = &mut islands[island_of_root[root]];
+        local_index[instance.index] = island.bodies.len();
+        island.bodies.push(instance.index);
+    }
+    for island in &mut islands {
+        island.awake_count = island.bodies.len();
+    }
+
+    let collision_count = collisions.len();
+    for (i, (a, b)) in links().enumerate() {
+        //joints between two sleeping or static bodies have nothing to do
+        let awake_index = match (is_awake(a), is_awake(b)) {
+            (true, _) => a,
+            (false, true) => b,
+            (false, false) => continue,
+        };
+        let island = &mut islands[island_of_root[union_find.find(awake_index)]];
+        if i < collision_count {
+            island.collisions.push(i);
+        } else {
+            island.joints.push(i - collision_count);
+        }
+        for index in [a, b] {
+            //static bodies get a copy per island they touch
+            if !is_awake(index) && !island.bodies[island.awake_count..].contains(&index) {
+                island.bodies.push(index);
+            }
+        }
+    }
+
+    for island in &mut islands {
+        let statics = &island.bodies[island.awake_count..];
+        let local = |index: usize| {
+            if is_awake(index) {
+                local_index[index]
+            } else {
+                island.awake_count + statics.iter().position(|&x| x == index).unwrap()
+            }
+        };
+        island.pairs = island
+            .collisions
+            .iter()
+            .map(|&i| {
+                (
+                    local(collisions[i].instance_index),
+                    local(collisions[i].other_index),
+                )
+            })
+            .collect();
+        island.joint_pairs = island
+            .joints
+            .iter()
+            .map(|&i| (local(joint_bodies[i].0), local(joint_bodies[i].1)))
+            .collect();
+        //taller stacks need more iterations for the impulse to propagate
+        island.iterations = island.awake_count.clamp(
+            settings.solver_iterations,
+            settings
+                .max_solver_iterations
+                .max(settings.solver_iterations),
+        );
+    }
+    islands
+}
+
+//returns the most iterations any island used
+pub fn solve_islands(
+    islands: &[Island],
+    collisions: &mut [CollisionInfo],
+    joints: &mut Arena<Joint>,
+    instances: &mut Arena<Cuboid>,
+    settings: &WorldSettings,
+) -> usize {
+    let mut workers: Vec<Vec<&Island>> = vec![Vec::new(); settings.solver_threads.max(1)];
+    let mut worker_load = vec![0; workers.len()];
+    let mut sorted: Vec<&Island> = islands.iter().filter(|x| x.is_connected()).collect();
+    //largest first onto the least loaded worker
+    let load = |x: &Island| (x.collisions.len() + x.joints.len()) * x.iterations;
+    sorted.sort_by_key(|x| std::cmp::Reverse(load(x)));
+    for island in sorted {
+        let worker = (0..workers.len()).min_by_key(|&i| worker_load[i]).unwrap();
+        worker_load[worker] += load(island);
+        workers[worker].push(island);
+    }
+
+    //every collision and joint belongs to at most one island, so each can be handed out mutably
+    //once
+    let mut slots: Vec<Option<&mut CollisionInfo>> = collisions.iter_mut().map(Some).collect();
+    let mut joint_slots: Vec<Option<&mut Joint>> = joints.iter_mut().map(Some).collect();
+    type Work<'a, 'b> = (&'a Island, Vec<IslandContact<'b>>, Vec<IslandJoint<'b>>);
+    let work: Vec<Vec<Work>> = workers
+        .into_iter()
+        .map(|islands| {
+            islands
+                .into_iter()
+                .map(|island| {
+                    let contacts = island
+                        .collisions
+                        .iter()
+                        .zip(&island.pairs)
+                        .map(|(&i, &(a, b))| IslandContact {
+                            a,
+                            b,
+                            info: slots[i].take().unwrap(),
+                        })
+                        .collect();
+                    let joints = island
+                        .joints
+                        .iter()
+                        .zip(&island.joint_pairs)
+                        .map(|(&i, &(a, b))| IslandJoint {
+                            a,
+                            b,
+                            joint: joint_slots[i].take().unwrap(),
+                        })
+                        .collect();
+                    (island, contacts, joints)
+                })
+                .collect()
+        })
+        .collect();
+
+    let instances_ref = &*instances;
+    let solve = |islands: Vec<Work>| -> Vec<(usize, Vec3, Vec3)> {
+        let mut velocities = Vec::new();
+        for (island, mut contacts, mut joints) in islands {
+            let mut bodies: Vec<Cuboid> = island
+                .bodies
+                .iter()
+                .map(|&i| match i {
+                    WORLD_SLOT => world_anchor(),
+                    _ => instances_ref.get_by_index(i).unwrap().clone(),
+                })
+                .collect();
+            resolve_collisions(
+                &mut contacts,
+                &mut joints,
+                &mut bodies,
+                settings,
+                island.iterations,
+            );
+            for (&index, body) in island.bodies.iter().zip(&bodies).take(island.awake_count) {
+                velocities.push((index, body.velocity, body.angular_velocity));
+            }
+        }
+        velocities
+    };
+    let results: Vec<Vec<(usize, Vec3, Vec3)>> = if work.len() == 1 {
+        work.into_iter().map(solve).collect()
+    } else {
+        thread::scope(|scope| {
+            let solve = &solve;
+            let handles: Vec<_> = work
+                .into_iter()
+                .map(|islands| scope.spawn(move || solve(islands)))
+                .collect();
+            handles.into_iter().map(|x| x.join().unwrap()).collect()
+        })
+    };
+
+    for (index, velocity, angular_velocity) in results.into_iter().flatten() {
+        let instance = instances.get_mut_by_index(index).unwrap();
+        instance.velocity = velocity;
+        instance.angular_velocity = angular_velocity;
+    }
+    islands
+        .iter()
+        .filter(|x| x.is_connected())
+        .map(|x| x.iterations)
+        .max()
+        .unwrap_or(0)
+}
+
+//disjoint set over body slots, bodies joined by a contact end up with the same root
+#[derive(Debug)]
+pub struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl UnionFind {
+    pub fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+            rank: vec![0; n],
+        }
+    }
+
+    pub fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            //path halving
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    pub fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        match self.rank[a].cmp(&self.rank[b]) {
+            std::cmp::Ordering::Less => self.parent[a] = b,
+            std::cmp::Ordering::Greater => self.parent[b] = a,
+            std::cmp::Ordering::Equal => {
+                self.parent[b] = a;
+                self.rank[a] += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scenes::Scene,
+        world::{World, WorldSettings},
+    };
+
+    fn stack(world: &mut World, x: f32, height: usize) {
+        for i in 0..height {
+            world.add_body(Cuboid {
+                position: Vec3 {
+                    x,
+                    y: 0.5 + i as f32 * 0.99, //slightly overlapping
+                    z: 0.0,
+                },
+                ..Default::default()
+            });
+        }
+    }
+
+    #[test]
+    fn test_islands_split_at_static_bodies() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        stack(&mut world, 0.0, 3);
+        stack(&mut world, 5.0, 2);
+        world.update();
+        let mut islands: Vec<&Island> = world.islands().iter().collect();
+        islands.sort_by_key(|x| x.awake_count);
+        assert_eq!(islands.len(), 2);
+        assert_eq!(islands[0].awake_count, 2);
+        assert_eq!(islands[1].awake_count, 3);
+        for island in islands {
+            //both share the floor, but each gets its own copy
+            assert_eq!(island.bodies.len(), island.awake_count + 1);
+            assert_eq!(island.pairs.len(), island.collisions.len());
+        }
+    }
+
+    #[test]
+    fn test_tall_islands_get_more_iterations() {
+        let settings = WorldSettings::default();
+        let mut world = World::new(Scene::Grid, 0, settings);
+        stack(&mut world, 0.0, 20);
+        stack(&mut world, 5.0, 1);
+        world.update();
+        let mut islands: Vec<&Island> = world.islands().iter().collect();
+        islands.sort_by_key(|x| x.awake_count);
+        assert_eq!(islands[0].iterations, settings.solver_iterations);
+        assert_eq!(islands[1].iterations, 20);
+        assert_eq!(world.stats().solver_iterations, 20);
+    }
+
+    #[test]
+    fn test_threaded_matches_serial() {
+        let run = |solver_threads| {
+            let mut world = World::new(
+                Scene::Grid,
+                0,
+                WorldSettings {
+                    solver_threads,
+                    ..Default::default()
+                },
+            );
+            for i in 0..4 {
+                stack(&mut world, i as f32 * 3.0, 3);
+            }
+            for _ in 0..30 {
+                world.update();
+            }
+            world.bodies().map(|x| x.position).collect::<Vec<_>>()
+        };
+        assert_eq!(run(1), run(3));
+    }
+
+    #[test]
+    fn test_union_find() {
+        let mut uf = UnionFind::new(5);
+        uf.union(0, 1);
+        uf.union(3, 4);
+        uf.union(1, 4);
+        assert_eq!(uf.find(0), uf.find(3));
+        assert_ne!(uf.find(0), uf.find(2));
+    }
+}
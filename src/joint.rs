@@ -0,0 +1,480 @@
+use crate::{
+    arena::Handle,
+    math::{EpsilonEquals, Mat3, Quaternion, Vec3},
+    physics::{
+        apply_angular_impulse, apply_impulse, relative_velocity, solver_mass, tangent_basis,
+    },
+    world::{BodyHandle, Cuboid, GLOBAL_AXES, WorldSettings},
+};
+
+pub type JointHandle = Handle;
+//the slot joints to the world are solved against, standing in for a static body at the origin
+//that isn't in the world's arena, so there's nothing there to collide with or remove
+pub(crate) const WORLD_SLOT: usize = usize::MAX;
+//prismatic joints use the most, 3 angular, 2 across the axis and 2 limits
+const MAX_ROWS: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JointKind {
+    //holds them exactly as they were, like a weld
+    Fixed,
+    //turns only about axis, optionally between limits in radians from where it started. limits
+    //are of b relative to a, so b turning the right handed way about axis is positive
+    Revolute {
+        axis: Vec3,
+        limits: Option<(f32, f32)>,
+    },
+    //turns any way about the anchor, a ball and socket
+    Spherical,
+    //slides only along axis without turning, optionally between limits in metres from where it
+    //started, again b relative to a
+    Prismatic {
+        axis: Vec3,
+        limits: Option<(f32, f32)>,
+    },
+    //keeps the anchors between min and max apart. a rod when they're equal, a rope when min is 0
+    Distance {
+        min: f32,
+        max: f32,
+    },
+}
+
+//see World::add_joint(). joined bodies don't collide with each other
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub kind: JointKind, //axes are in a's local space
+    pub a: BodyHandle,
+    pub b: Option<BodyHandle>, //None joins a to the world
+    pub anchor_a: Vec3,        //local to a, from its position
+    pub anchor_b: Vec3,        //local to b, or world space for the world
+    pub reference: Quaternion, //b's rotation relative to a's when they were joined
+    //accumulated by the solver, carried into the next tick for warm starting
+    pub point_impulse: Vec3,
+    pub impulses: [f32; MAX_ROWS],
+}
+
+impl Joint {
+    //anchors and axes in world space, as the bodies are now. b is None for the world. None if an
+    //axis has no direction
+    pub(crate) fn new(
+        kind: JointKind,
+        (a_handle, a): (BodyHandle, &Cuboid),
+        b: Option<(BodyHandle, &Cuboid)>,
+        anchor_a: Vec3,
+        anchor_b: Vec3,
+    ) -> Option<Self> {
+        let local = |axis: Vec3| Some(axis.normalize()?.rotate(a.rotation.conj()));
+        let kind = match kind {
+            JointKind::Revolute { axis, limits } => JointKind::Revolute {
+                axis: local(axis)?,
+                limits,
+            },
+            JointKind::Prismatic { axis, limits } => JointKind::Prismatic {
+                axis: local(axis)?,
+                limits,
+            },
+            _ => kind,
+        };
+        let (b_rotation, anchor_b) = match b {
+            Some((_, b)) => (
+                b.rotation,
+                (anchor_b - b.position).rotate(b.rotation.conj()),
+            ),
+            None => (Quaternion::default(), anchor_b),
+        };
+        Some(Self {
+            kind,
+            a: a_handle,
+            b: b.map(|x| x.0),
+            anchor_a: (anchor_a - a.position).rotate(a.rotation.conj()),
+            anchor_b,
+            reference: a.rotation.conj() * b_rotation,
+            point_impulse: Vec3::default(),
+            impulses: [0.0; MAX_ROWS],
+        })
+    }
+}
+
+pub(crate) fn world_anchor() -> Cuboid {
+    let mut anchor = Cuboid {
+        frozen: true,
+        ..Default::default()
+    };
+    anchor.update_derived();
+    anchor
+}
+
+//one degree of freedom taken away, the relative velocity along dir, either linear at the lever
+//arms or angular
+#[derive(Clone, Copy, Default)]
+struct Row {
+    dir: Vec3,
+    arms: Option<(Vec3, Vec3)>, //None for angular rows
+    mass: f32,
+    velocity_bias: f32,
+    one_sided: bool, //limits only ever push, like contacts
+    impulse: f32,
+}
+
+//precomputed once per tick like a contact constraint, the impulses are written back after
+pub(crate) struct JointConstraint {
+    //the anchors are pinned together as a 3x3 block rather than three rows, which converges much
+    //faster down a chain
+    point: Option<(Vec3, Vec3, Mat3, Vec3)>, //lever arms, inverse effective mass, velocity bias
+    point_impulse: Vec3,
+    rows: [Option<Row>; MAX_ROWS],
+}
+
+impl JointConstraint {
+    pub(crate) fn new(joint: &Joint, a: &Cuboid, b: &Cuboid, settings: &WorldSettings) -> Self {
+        let dt = settings.dt;
+        let bias = settings.baumgarte_bias / dt;
+        let anchor_a = a.position + joint.anchor_a.rotate(a.rotation);
+        let anchor_b = b.position + joint.anchor_b.rotate(b.rotation);
+        let (r1, r2) = (anchor_a - a.centre_of_mass(), anchor_b - b.centre_of_mass());
+        //b's rotation away from where it should be relative to a, as a world space rotation
+        //vector. it grows at b's angular velocity minus a's
+        let mut error = b.rotation * (a.rotation * joint.reference).conj();
+        //the same rotation, the short way round
+        if error.real < 0.0 {
+            error = Quaternion {
+                real: -error.real,
+                x: -error.x,
+                y: -error.y,
+                z: -error.z,
+            };
+        }
+        let twist = error.to_vec3() * 2.0;
+
+        let mut rows = Vec::new();
+        let angular = |dir: Vec3, velocity_bias: f32, one_sided: bool| Row {
+            dir,
+            mass: inverse(angular_mass(a, b, dir)),
+            velocity_bias,
+            one_sided,
+            ..Default::default()
+        };
+        let linear = |dir: Vec3, arms: (Vec3, Vec3), velocity_bias: f32, one_sided: bool| Row {
+            dir,
+            arms: Some(arms),
+            mass: inverse(linear_mass(a, b, arms, dir)),
+            velocity_bias,
+            one_sided,
+            ..Default::default()
+        };
+        //closing no more than the gap this tick while there's one left, pushed back out when
+        //it's gone, like a speculative contact
+        let limit = |gap: f32| if gap > 0.0 { -gap / dt } else { -gap * bias };
+        let lock_rotation = |rows: &mut Vec<Row>| {
+            for dir in GLOBAL_AXES {
+                rows.push(angular(dir, twist.dot(&dir) * bias, false));
+            }
+        };
+        let mut point = true;
+        match joint.kind {
+            JointKind::Fixed => lock_rotation(&mut rows),
+            JointKind::Spherical => (),
+            JointKind::Revolute { axis, limits } => {
+                let hinge = axis.rotate(a.rotation);
+                let hinge_b = axis.rotate(joint.reference.conj()).rotate(b.rotation);
+                let bend = hinge.cross(&hinge_b);
+                for dir in tangent_basis(hinge) {
+                    rows.push(angular(dir, bend.dot(&dir) * bias, false));
+                }
+                if let Some((min, max)) = limits {
+                    let angle = 2.0 * error.to_vec3().dot(&hinge).atan2(error.real);
+                    rows.push(angular(-hinge, limit(angle - min), true));
+                    rows.push(angular(hinge, limit(max - angle), true));
+                }
+            }
+            JointKind::Prismatic { axis, limits } => {
+                point = false;
+                lock_rotation(&mut rows);
+                let slide = axis.rotate(a.rotation);
+                let offset = anchor_b - anchor_a;
+                //a's velocity is measured at b's anchor, which is what drifts off the axis
+                let arms = (anchor_b - a.centre_of_mass(), r2);
+                for dir in tangent_basis(slide) {
+                    rows.push(linear(dir, arms, offset.dot(&dir) * bias, false));
+                }
+                if let Some((min, max)) = limits {
+                    let along = offset.dot(&slide);
+                    rows.push(linear(-slide, arms, limit(along - min), true));
+                    rows.push(linear(slide, arms, limit(max - along), true));
+                }
+            }
+            JointKind::Distance { min, max } => {
+                point = false;
+                let offset = anchor_a - anchor_b;
+                if let Some(dir) = offset.normalize() {
+                    let length = offset.mag();
+                    rows.push(linear(dir, (r1, r2), limit(length - min), true));
+                    rows.push(linear(-dir, (r1, r2), limit(max - length), true));
+                }
+            }
+        }
+
+        let mut constraint = Self {
+            point: point.then(|| {
+                //each column is how fast the anchors separate per unit impulse along that axis
+                let mut k = Mat3::zero();
+                for (i, dir) in GLOBAL_AXES.into_iter().enumerate() {
+                    let column = impulse_response(a, b, (r1, r2), dir);
+                    k.array[i * 3..i * 3 + 3].copy_from_slice(&[column.x, column.y, column.z]);
+                }
+                let mass = k.inverse().unwrap_or(Mat3::zero());
+                (r1, r2, mass, (anchor_b - anchor_a) * bias)
+            }),
+            point_impulse: Vec3::default(),
+            rows: [None; MAX_ROWS],
+        };
+        for (slot, row) in constraint.rows.iter_mut().zip(rows) {
+            *slot = Some(row);
+        }
+        if settings.warm_starting {
+            constraint.point_impulse = joint.point_impulse;
+            for (row, impulse) in constraint.rows.iter_mut().flatten().zip(joint.impulses) {
+                row.impulse = impulse;
+            }
+        }
+        constraint
+    }
+
+    pub(crate) fn warm_start(&self, a: &mut Cuboid, b: &mut Cuboid) {
+        if let Some((r1, r2, ..)) = self.point {
+            apply_impulse(a, b, r1, r2, self.point_impulse);
+        }
+        for row in self.rows.iter().flatten() {
+            row.apply(a, b, row.impulse);
+        }
+    }
+
+    pub(crate) fn solve(&mut self, a: &mut Cuboid, b: &mut Cuboid) {
+        for row in self.rows.iter_mut().flatten() {
+            let velocity = row.velocity(a, b);
+            let mut new_impulse = row.impulse + row.mass * (row.velocity_bias - velocity);
+            if row.one_sided {
+                new_impulse = new_impulse.max(0.0);
+            }
+            let delta = new_impulse - row.impulse;
+            row.impulse = new_impulse;
+            row.apply(a, b, delta);
+        }
+        if let Some((r1, r2, mass, velocity_bias)) = self.point {
+            let velocity = relative_velocity(a, b, r1, r2);
+            let impulse = &mass * &(velocity_bias - velocity);
+            self.point_impulse += impulse;
+            apply_impulse(a, b, r1, r2, impulse);
+        }
+    }
+
+    pub(crate) fn store(&self, joint: &mut Joint) {
+        joint.point_impulse = self.point_impulse;
+        for (impulse, row) in joint.impulses.iter_mut().zip(&self.rows) {
+            *impulse = row.map_or(0.0, |x| x.impulse);
+        }
+    }
+}
+
+impl Row {
+    fn velocity(&self, a: &Cuboid, b: &Cuboid) -> f32 {
+        match self.arms {
+            Some((r1, r2)) => relative_velocity(a, b, r1, r2).dot(&self.dir),
+            None => (a.angular_velocity - b.angular_velocity).dot(&self.dir),
+        }
+    }
+    fn apply(&self, a: &mut Cuboid, b: &mut Cuboid, impulse: f32) {
+        match self.arms {
+            Some((r1, r2)) => apply_impulse(a, b, r1, r2, self.dir * impulse),
+            None => apply_angular_impulse(a, b, self.dir * impulse),
+        }
+    }
+}
+
+//how fast a's point separates from b's along anything, per unit impulse along dir
+fn impulse_response(a: &Cuboid, b: &Cuboid, (r1, r2): (Vec3, Vec3), dir: Vec3) -> Vec3 {
+    let (inv_m1, inv_moi1) = solver_mass(a);
+    let (inv_m2, inv_moi2) = solver_mass(b);
+    dir * (inv_m1 + inv_m2)
+        + (&inv_moi1 * &r1.cross(&dir)).cross(&r1)
+        + (&inv_moi2 * &r2.cross(&dir)).cross(&r2)
+}
+
+fn linear_mass(a: &Cuboid, b: &Cuboid, arms: (Vec3, Vec3), dir: Vec3) -> f32 {
+    impulse_response(a, b, arms, dir).dot(&dir)
+}
+
+fn angular_mass(a: &Cuboid, b: &Cuboid, dir: Vec3) -> f32 {
+    let (_, inv_moi1) = solver_mass(a);
+    let (_, inv_moi2) = solver_mass(b);
+    (&inv_moi1 * &dir).dot(&dir) + (&inv_moi2 * &dir).dot(&dir)
+}
+
+fn inverse(k: f32) -> f32 {
+    if k.epsilon_equals(0.0) { 0.0 } else { 1.0 / k }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scenes::Scene, shape::Shape, world::World};
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    #[test]
+    fn test_pendulum_keeps_its_length() {
+        //world joints don't need anything there to hang from
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        let floor = world.instances.handle_at(0).unwrap();
+        world.remove_body(floor).unwrap();
+        let pivot = vec3(0.0, 5.0, 0.0);
+        let start = vec3(2.0, 5.0, 0.0);
+        let bob = world.add_body(Cuboid {
+            position: start,
+            shape: Shape::Sphere { radius: 0.25 },
+            ..Default::default()
+        });
+        let joint = world
+            .add_joint(JointKind::Spherical, bob, None, pivot, pivot)
+            .unwrap();
+        assert!(
+            world
+                .add_joint(JointKind::Spherical, bob, Some(bob), pivot, pivot)
+                .is_none()
+        );
+        let still = JointKind::Revolute {
+            axis: Vec3::default(),
+            limits: None,
+        };
+        assert!(world.add_joint(still, bob, None, pivot, pivot).is_none());
+        assert_eq!(world.joints().count(), 1);
+        let mut lowest = f32::INFINITY;
+        for _ in 0..360 {
+            world.update();
+            let body = world.body(bob).unwrap();
+            //held at the pivot, not at its centre, so it swings round on a 2m arm
+            assert!(((body.position - pivot).mag() - 2.0).abs() < 0.02);
+            lowest = lowest.min(body.position.y);
+        }
+        assert!(lowest < 3.1);
+
+        assert!(world.remove_joint(joint).is_some());
+        assert!(world.joint(joint).is_none());
+        for _ in 0..60 {
+            world.update();
+        }
+        assert!((world.body(bob).unwrap().position - pivot).mag() > 2.1);
+    }
+
+    #[test]
+    fn test_hinge_stops_at_limits() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        let post = world.add_body(Cuboid {
+            position: vec3(0.0, 2.0, 0.0),
+            scale: vec3(0.2, 4.0, 0.2),
+            frozen: true,
+            ..Default::default()
+        });
+        //a plank sticking out sideways from the top of the post, hinged so it can droop down
+        //and forwards but only so far
+        let plank = world.add_body(Cuboid {
+            position: vec3(1.0, 4.0, 0.0),
+            scale: vec3(2.0, 0.1, 0.4),
+            ..Default::default()
+        });
+        let hinge = vec3(0.0, 4.0, 0.0);
+        let axis = vec3(0.0, 0.0, 1.0);
+        world
+            .add_joint(
+                JointKind::Revolute {
+                    axis,
+                    limits: Some((-0.5, 0.5)),
+                },
+                post,
+                Some(plank),
+                hinge,
+                hinge,
+            )
+            .unwrap();
+        for _ in 0..360 {
+            world.update();
+        }
+        //gravity turns it clockwise about z, which is negative
+        let body = world.body(plank).unwrap();
+        let (angle, turned) = {
+            let v = body.rotation.to_vec3();
+            (2.0 * v.z.atan2(body.rotation.real), v)
+        };
+        assert!((angle + 0.5).abs() < 0.02, "{angle}");
+        assert!(turned.x.abs() < 1e-3 && turned.y.abs() < 1e-3);
+        let tip = body.position + vec3(1.0, 0.0, 0.0).rotate(body.rotation);
+        assert!((tip - hinge).mag() > 1.95);
+        assert!((body.position + vec3(-1.0, 0.0, 0.0).rotate(body.rotation) - hinge).mag() < 0.02);
+    }
+
+    #[test]
+    fn test_weld_slider_and_rope() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        let welded = world.add_body(Cuboid {
+            position: vec3(-3.0, 3.0, 0.0),
+            ..Default::default()
+        });
+        let anchor = vec3(-3.0, 3.5, 0.0);
+        world
+            .add_joint(JointKind::Fixed, welded, None, anchor, anchor)
+            .unwrap();
+        //sliding down a 45 degree rail until it reaches the end. the world's b, so it's the world
+        //that moves back up the rail relative to the body
+        let diagonal = vec3(1.0, -1.0, 0.0).normalize().unwrap();
+        let slider = world.add_body(Cuboid {
+            position: vec3(0.0, 5.0, 0.0),
+            ..Default::default()
+        });
+        world
+            .add_joint(
+                JointKind::Prismatic {
+                    axis: diagonal,
+                    limits: Some((-1.5, 1.0)),
+                },
+                slider,
+                None,
+                vec3(0.0, 5.0, 0.0),
+                vec3(0.0, 5.0, 0.0),
+            )
+            .unwrap();
+        //a rope lets it fall until it's taut
+        let hanging = world.add_body(Cuboid {
+            position: vec3(3.0, 4.0, 0.0),
+            ..Default::default()
+        });
+        let rope = world
+            .add_joint(
+                JointKind::Distance { min: 0.0, max: 2.0 },
+                hanging,
+                None,
+                vec3(3.0, 4.5, 0.0),
+                vec3(3.0, 5.0, 0.0),
+            )
+            .unwrap();
+        for _ in 0..360 {
+            world.update();
+        }
+        let body = world.body(welded).unwrap();
+        assert!((body.position - vec3(-3.0, 3.0, 0.0)).mag() < 0.01);
+        assert!(body.rotation.to_vec3().mag() < 1e-3);
+        let body = world.body(slider).unwrap();
+        let travelled = body.position - vec3(0.0, 5.0, 0.0);
+        assert!((travelled.dot(&diagonal) - 1.5).abs() < 0.02);
+        assert!((travelled - diagonal * 1.5).mag() < 0.02);
+        assert!(body.rotation.to_vec3().mag() < 1e-3);
+        let body = world.body(hanging).unwrap();
+        assert!((body.position.y - 2.5).abs() < 0.02);
+
+        //removing a body takes its joints with it
+        world.remove_body(hanging);
+        assert!(world.joint(rope).is_none());
+        assert_eq!(world.joints().count(), 2);
+    }
+}
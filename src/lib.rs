@@ -1,8 +1,20 @@
+pub mod arena;
 pub mod camera;
+pub mod cli;
+pub mod compound;
+pub mod gjk;
 pub mod hash_grid;
+pub mod heightfield;
+pub mod hull;
+pub mod island;
+pub mod joint;
+pub mod material;
 pub mod math;
 pub mod physics;
 pub mod scenes;
+pub mod shape;
+pub mod stats;
+pub mod trimesh;
 pub mod window;
 pub mod world;
 
@@ -179,37 +191,172 @@ pub const CUBE_INDICES: &[u16] = &[
     22,23,20
 ];
 
-pub const FLOOR_VERTICES: &[Vertex] = &[
-    //triangle 1
-    Vertex {
-        position: [500.0, 0.0, 500.0],
-        colour: [0.38, 0.38, 0.38],
-        normal: [0.0, 1.0, 0.0],
-    },
-    Vertex {
-        position: [500.0, 0.0, -500.0],
-        colour: [0.38, 0.38, 0.38],
-        normal: [0.0, 1.0, 0.0],
-    },
+//unit diameter like the cube, so the same model matrix sizes it. alternate sectors are shaded
+//darker, otherwise a rolling ball looks like it's sliding
+pub fn sphere_mesh(stacks: u16, sectors: u16) -> (Vec<Vertex>, Vec<u16>) {
+    use std::f32::consts::PI;
+    let mut vertices = Vec::with_capacity((stacks * sectors * 4) as usize);
+    let mut indices = Vec::with_capacity((stacks * sectors * 6) as usize);
+    for i in 0..stacks {
+        for j in 0..sectors {
+            let colour = if j % 2 == 0 {
+                [0.1176, 0.5647, 1.0]
+            } else {
+                [0.0706, 0.3412, 0.6]
+            };
+            //each quad gets its own corners so the colours don't blend across it
+            let first = vertices.len() as u16;
+            for (stack, sector) in [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)] {
+                let polar = PI * stack as f32 / stacks as f32;
+                let azimuth = 2.0 * PI * sector as f32 / sectors as f32;
+                let normal = [
+                    polar.sin() * azimuth.cos(),
+                    polar.cos(),
+                    -polar.sin() * azimuth.sin(),
+                ];
+                vertices.push(Vertex {
+                    position: normal.map(|x| x * 0.5),
+                    colour,
+                    normal,
+                });
+            }
+            //ccw from outside, the poles' quads are degenerate triangles which is harmless
+            indices.extend([first, first + 1, first + 2, first + 2, first + 3, first]);
+        }
+    }
+    (vertices, indices)
+}
+
+//unit diameter and height along y, open ended since capsules cap it with spheres
+pub fn cylinder_mesh(sectors: u16) -> (Vec<Vertex>, Vec<u16>) {
+    use std::f32::consts::PI;
+    let mut vertices = Vec::with_capacity((sectors * 4) as usize);
+    let mut indices = Vec::with_capacity((sectors * 6) as usize);
+    for j in 0..sectors {
+        let colour = if j % 2 == 0 {
+            [0.1176, 0.5647, 1.0]
+        } else {
+            [0.0706, 0.3412, 0.6]
+        };
+        let first = vertices.len() as u16;
+        for (y, sector) in [(0.5, j), (-0.5, j), (-0.5, j + 1), (0.5, j + 1)] {
+            let azimuth = 2.0 * PI * sector as f32 / sectors as f32;
+            let normal = [azimuth.cos(), 0.0, -azimuth.sin()];
+            vertices.push(Vertex {
+                position: [normal[0] * 0.5, y, normal[2] * 0.5],
+                colour,
+                normal,
+            });
+        }
+        indices.extend([first, first + 1, first + 2, first + 2, first + 3, first]);
+    }
+    (vertices, indices)
+}
+
+//flat shaded, in the hull's own units since it isn't scaled
+pub fn hull_mesh(hull: &hull::ConvexHull) -> (Vec<Vertex>, Vec<u16>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (i, (face, normal)) in hull.faces.iter().zip(&hull.normals).enumerate() {
+        let colour = if i % 2 == 0 {
+            [0.5451, 0.4510, 0.3333]
+        } else {
+            [0.4, 0.3294, 0.2431]
+        };
+        let first = vertices.len() as u16;
+        for v in face.iter().map(|&x| hull.vertices[x]) {
+            vertices.push(Vertex {
+                position: [v.x, v.y, v.z],
+                colour,
+                normal: [normal.x, normal.y, normal.z],
+            });
+        }
+        //faces are convex and already ccw from outside, so a fan covers them
+        for j in 1..face.len() as u16 - 1 {
+            indices.extend([first, first + j, first + j + 1]);
+        }
+    }
+    (vertices, indices)
+}
+
+//flat shaded like hulls, but with u32 indices since levels easily have more vertices than u16
+//can count
+pub fn trimesh_mesh(mesh: &trimesh::TriMesh) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(mesh.triangles.len() * 3);
+    for (i, normal) in mesh.normals.iter().enumerate() {
+        let colour = if i % 2 == 0 {
+            [0.45, 0.45, 0.45]
+        } else {
+            [0.4, 0.4, 0.4]
+        };
+        for v in mesh.corners(i) {
+            vertices.push(Vertex {
+                position: [v.x, v.y, v.z],
+                colour,
+                normal: [normal.x, normal.y, normal.z],
+            });
+        }
+    }
+    let indices = (0..vertices.len() as u32).collect();
+    (vertices, indices)
+}
+
+//smooth shaded, each sample's normal averaged from the triangles round it, and shaded from grass
+//to rock by height
+pub fn heightfield_mesh(field: &heightfield::Heightfield) -> (Vec<Vertex>, Vec<u32>) {
+    let mut normals = vec![math::Vec3::default(); field.heights.len()];
+    for t in 0..field.triangle_count() {
+        for (i, j) in field.samples(t) {
+            normals[i * field.rows + j] += field.normals[t];
+        }
+    }
+    let range = (field.max.y - field.min.y).max(math::EPSILON);
+    let mut vertices = Vec::with_capacity(field.heights.len());
+    for i in 0..field.columns {
+        for j in 0..field.rows {
+            let v = field.vertex(i, j);
+            let normal = normals[i * field.rows + j]
+                .normalize()
+                .unwrap_or(math::Vec3 {
+                    x: 0.0,
+                    y: 1.0,
+                    z: 0.0,
+                });
+            let t = (v.y - field.min.y) / range;
+            vertices.push(Vertex {
+                position: [v.x, v.y, v.z],
+                colour: [0.3 + 0.15 * t, 0.42 - 0.05 * t, 0.25 + 0.15 * t],
+                normal: [normal.x, normal.y, normal.z],
+            });
+        }
+    }
+    let indices = (0..field.triangle_count())
+        .flat_map(|t| field.samples(t).map(|(i, j)| (i * field.rows + j) as u32))
+        .collect();
+    (vertices, indices)
+}
+
+//a unit square in the xz plane facing up, scaled out to draw planes
+pub const PLANE_VERTICES: &[Vertex] = &[
     Vertex {
-        position: [-500.0, 0.0, -500.0],
+        position: [0.5, 0.0, 0.5],
         colour: [0.38, 0.38, 0.38],
         normal: [0.0, 1.0, 0.0],
     },
-    //triangle 2
     Vertex {
-        position: [-500.0, 0.0, -500.0],
+        position: [0.5, 0.0, -0.5],
         colour: [0.38, 0.38, 0.38],
         normal: [0.0, 1.0, 0.0],
     },
     Vertex {
-        position: [-500.0, 0.0, 500.0],
+        position: [-0.5, 0.0, -0.5],
         colour: [0.38, 0.38, 0.38],
         normal: [0.0, 1.0, 0.0],
     },
     Vertex {
-        position: [500.0, 0.0, 500.0],
+        position: [-0.5, 0.0, 0.5],
         colour: [0.38, 0.38, 0.38],
         normal: [0.0, 1.0, 0.0],
     },
 ];
+pub const PLANE_INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
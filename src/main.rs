@@ -1,5 +1,12 @@
+use physics_engine::{cli::Args, world::WorldSettings};
+
 fn main() {
     env_logger::init();
+    let args = Args::from_env();
     println!("press enter to toggle simulation");
-    physics_engine::window::init();
+    let settings = WorldSettings {
+        solver_threads: args.threads,
+        ..Default::default()
+    };
+    physics_engine::window::init(args.scene, args.count, settings);
 }
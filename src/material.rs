@@ -0,0 +1,111 @@
+//when two materials disagree on a rule, the later variant wins (same priority order as physx)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CombineRule {
+    #[default]
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl CombineRule {
+    pub fn combine(&self, a: f32, b: f32) -> f32 {
+        match self {
+            CombineRule::Average => (a + b) / 2.0,
+            CombineRule::Min => a.min(b),
+            CombineRule::Multiply => a * b,
+            CombineRule::Max => a.max(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    pub restitution: f32,
+    pub static_friction: f32,
+    pub dynamic_friction: f32, //used once the tangent impulse leaves the static cone
+    pub restitution_combine: CombineRule,
+    pub friction_combine: CombineRule,
+}
+
+//coefficients for one contact pair
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CombinedMaterial {
+    pub restitution: f32,
+    pub static_friction: f32,
+    pub dynamic_friction: f32,
+}
+
+impl Material {
+    pub const CONCRETE: Material = Material {
+        restitution: 0.2,
+        static_friction: 0.9,
+        dynamic_friction: 0.7,
+        restitution_combine: CombineRule::Average,
+        friction_combine: CombineRule::Average,
+    };
+    pub const ICE: Material = Material {
+        restitution: 0.05,
+        static_friction: 0.05,
+        dynamic_friction: 0.02,
+        restitution_combine: CombineRule::Average,
+        friction_combine: CombineRule::Min,
+    };
+    pub const RUBBER: Material = Material {
+        restitution: 0.85,
+        static_friction: 1.0,
+        dynamic_friction: 0.8,
+        restitution_combine: CombineRule::Max,
+        friction_combine: CombineRule::Average,
+    };
+
+    pub fn combine(&self, other: &Material) -> CombinedMaterial {
+        let restitution_rule = self.restitution_combine.max(other.restitution_combine);
+        let friction_rule = self.friction_combine.max(other.friction_combine);
+        let static_friction = friction_rule.combine(self.static_friction, other.static_friction);
+        CombinedMaterial {
+            restitution: restitution_rule.combine(self.restitution, other.restitution),
+            static_friction,
+            //kinetic friction can't exceed static
+            dynamic_friction: friction_rule
+                .combine(self.dynamic_friction, other.dynamic_friction)
+                .min(static_friction),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            restitution: 0.5,
+            static_friction: 0.6,
+            dynamic_friction: 0.5,
+            restitution_combine: CombineRule::Average,
+            friction_combine: CombineRule::Average,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine_rule_priority() {
+        let combined = Material::default().combine(&Material::default());
+        assert_eq!(combined.restitution, 0.5);
+        assert_eq!(combined.static_friction, 0.6);
+
+        //ice's Min beats the default Average
+        let combined = Material::ICE.combine(&Material::CONCRETE);
+        assert_eq!(combined.static_friction, 0.05);
+        assert_eq!(combined.dynamic_friction, 0.02);
+        assert_eq!(combined.restitution, (0.05 + 0.2) / 2.0);
+
+        //rubber's Max restitution wins regardless of order
+        let a = Material::RUBBER.combine(&Material::ICE);
+        let b = Material::ICE.combine(&Material::RUBBER);
+        assert_eq!(a, b);
+        assert_eq!(a.restitution, 0.85);
+    }
+}
@@ -206,7 +206,7 @@ impl Mul for Mat4 {
         ans
     }
 }
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Mat3 {
     pub array: [f32; 9],
 }
@@ -234,6 +234,45 @@ impl Mat3 {
             ]
         }
     }
+    pub fn diagonal(v: Vec3) -> Self {
+        let mut ans = Self::zero();
+        ans.array[0] = v.x;
+        ans.array[4] = v.y;
+        ans.array[8] = v.z;
+        ans
+    }
+    pub fn scale(&self, k: f32) -> Self {
+        Self {
+            array: self.array.map(|x| x * k),
+        }
+    }
+    pub fn determinant(&self) -> f32 {
+        let a = self.array;
+        a[0] * (a[4] * a[8] - a[7] * a[5]) - a[3] * (a[1] * a[8] - a[7] * a[2])
+            + a[6] * (a[1] * a[5] - a[4] * a[2])
+    }
+    //None if singular. no tolerance, small bodies have legitimately tiny inertia tensors
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let a = self.array;
+        //adjugate over determinant, cofactors of the transpose
+        let cofactor = |r1: usize, c1: usize, r2: usize, c2: usize| {
+            a[c1 * 3 + r1] * a[c2 * 3 + r2] - a[c2 * 3 + r1] * a[c1 * 3 + r2]
+        };
+        let mut ans = Self::zero();
+        for col in 0..3 {
+            for row in 0..3 {
+                //entry (row, col) of the inverse is cofactor (col, row) of the original
+                let (r1, r2) = ((col + 1) % 3, (col + 2) % 3);
+                let (c1, c2) = ((row + 1) % 3, (row + 2) % 3);
+                ans.array[col * 3 + row] = cofactor(r1, c1, r2, c2) / det;
+            }
+        }
+        Some(ans)
+    }
 }
 
 impl Default for Mat3 {
@@ -275,7 +314,7 @@ impl Mul<&Vec3> for &Mat3 {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Quaternion {
     pub real: f32,
     pub x: f32, //i
@@ -1,128 +1,222 @@
+use std::sync::Arc;
+
 use crate::{
-    math::{EPSILON, EpsilonEquals, Plane, Vec3},
-    world::Cuboid,
+    gjk::{self, Query},
+    hull::ConvexHull,
+    joint::{Joint, JointConstraint},
+    material::CombinedMaterial,
+    math::{EPSILON, EpsilonEquals, Mat3, Plane, Vec3},
+    shape::Shape,
+    world::{AABB, Cuboid, WorldSettings},
 };
-const MAX_MANIFOLD_VERTICES: usize = 8;
+pub const MAX_CONTACT_POINTS: usize = 4; //after reduce_manifold()
+const CCD_BISECTIONS: usize = 12; //the time of impact is found to within step/2^this
+const MAX_CCD_STEPS: usize = 256; //caps the sweep for degenerate, zero thickness bodies
+const FLAT_TOLERANCE: f32 = 0.05; //sine of how far a capsule can tilt and still lie flat
+const FACE_ALIGNMENT: f32 = 0.98; //cosine, hulls less face to face than this touch at one point
 
 #[derive(PartialEq, Debug)]
 pub enum CollisionType {
     Face,
     EdgeEdge(Vec3, Vec3),
+    Point, //closest or deepest points, one contact or two when something rounded lies flat
 }
 #[derive(Debug)]
 pub struct CollisionInfo {
     pub instance_index: usize,
     pub other_index: usize,
-    pub mtv: Vec3,
+    pub mtv: Vec3, //other->self, scaled by the overlap so zero for speculative contacts
+    pub normal: Vec3, //unit length other->self, set either way
     pub collision_type: CollisionType,
-    pub manifold: [Option<ContactPoint>; MAX_MANIFOLD_VERTICES],
+    pub manifold: [Option<ContactPoint>; MAX_CONTACT_POINTS],
+    //which triangle of a mesh or child of a compound (instance's, other's) it's against, 0
+    //otherwise. a body can touch several at once, each with its own collision
+    pub parts: (usize, usize),
+}
+
+impl CollisionInfo {
+    //seeds matching points with last tick's impulses, unmatched ones start cold
+    pub fn warm_start(&mut self, previous: &[Option<ContactPoint>; MAX_CONTACT_POINTS]) {
+        for point in self.manifold.iter_mut().flatten() {
+            if let Some(cached) = previous.iter().flatten().find(|x| x.id == point.id) {
+                point.normal_impulse = cached.normal_impulse;
+                point.tangent_impulse = cached.tangent_impulse;
+            }
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct ContactPoint {
     pub point: Vec3,
     pub depth: f32,
+    pub id: u32, //from the features that produced it, stable while the faces stay in contact
+    //accumulated by the solver, carried into the next tick by the contact cache
+    pub normal_impulse: f32,
+    pub tangent_impulse: Vec3, //world space, so it survives the tangent basis rotating
 }
-fn sat(instance: &Cuboid, other: &Cuboid) -> Option<(Vec3, CollisionType)> {
-    let mut edge_axes = [Vec3::default(); 9];
-    for i in 0..3 {
-        for j in 0..3 {
-            let cross = instance.face_axes[i].cross(&other.face_axes[j]);
-            if let Some(cross) = cross.normalize() {
-                edge_axes[i * 3 + j] = cross;
-            }
+//a later group of axes (other's faces, then edges) only wins when it's clearly shallower,
+//otherwise rounding flips near-equal axes from tick to tick
+const SAT_RELATIVE_TOLERANCE: f32 = 0.95;
+const SAT_ABSOLUTE_TOLERANCE: f32 = 0.0005;
+
+//separating_axis goes in as last tick's separating axis for this pair (if any) and comes out as
+//this tick's, or None if they collide
+fn sat(
+    instance: &Cuboid,
+    other: &Cuboid,
+    separating_axis: &mut Option<usize>,
+) -> Option<(Vec3, CollisionType)> {
+    let axes = sat_axes(instance, other);
+    //None if the axis separates them
+    let overlap_on = |axis: &Vec3| {
+        let (min1, max1) = project(instance, axis);
+        let (min2, max2) = project(other, axis);
+        if max1 < min2 || max2 < min1 {
+            None
+        } else {
+            Some(max1.min(max2) - min1.max(min2))
         }
-    }
-    let mut mtv: Vec3 = Vec3::default();
-    let mut min_overlap = f32::INFINITY;
-    let mut mtv_index = 0;
-    for (i, axis) in instance
-        .face_axes
-        .iter()
-        .chain(other.face_axes.iter())
-        .chain(edge_axes.iter())
-        .enumerate()
+    };
+
+    //whatever separated them last tick usually still does, so the other 14 can be skipped
+    if let Some(i) = *separating_axis
+        && axes[i].mag() > EPSILON
+        && overlap_on(&axes[i]).is_none()
     {
+        return None;
+    }
+    //best (index, overlap) per group: instance faces, other faces, edges
+    let mut best = [(0, f32::INFINITY); 3];
+    for (i, axis) in axes.iter().enumerate() {
         if axis.mag() <= EPSILON {
             continue;
         }
-        let projected_corners1 = instance.corners.map(|x| x.dot(axis));
-        let projected_corners2 = other.corners.map(|x| x.dot(axis));
-        let mut min1 = f32::INFINITY;
-        let mut min2 = f32::INFINITY;
-        let mut max1 = f32::NEG_INFINITY;
-        let mut max2 = f32::NEG_INFINITY;
-        for x in projected_corners1 {
-            min1 = min1.min(x);
-            max1 = max1.max(x);
+        let Some(overlap) = overlap_on(axis) else {
+            *separating_axis = Some(i);
+            return None;
+        };
+        let group = &mut best[(i / 3).min(2)];
+        if overlap < group.1 {
+            *group = (i, overlap);
         }
-        for x in projected_corners2 {
-            min2 = min2.min(x);
-            max2 = max2.max(x);
+    }
+    *separating_axis = None;
+    let (mut mtv_index, mut min_overlap) = best[0];
+    for (i, overlap) in &best[1..] {
+        if *overlap < min_overlap * SAT_RELATIVE_TOLERANCE - SAT_ABSOLUTE_TOLERANCE {
+            (mtv_index, min_overlap) = (*i, *overlap);
         }
-        if max1 < min2 || max2 < min1 {
-            return None;
+    }
+
+    Some((
+        orient(instance, other, axes[mtv_index]) * min_overlap,
+        collision_type(instance, other, mtv_index),
+    ))
+}
+
+//like sat(), but for a pair it already knows is apart: the axis they're furthest apart along,
+//oriented other->self, and the gap along it
+fn sat_gap(instance: &Cuboid, other: &Cuboid) -> (Vec3, f32, CollisionType) {
+    let axes = sat_axes(instance, other);
+    let mut best = [(0, f32::NEG_INFINITY); 3];
+    for (i, axis) in axes.iter().enumerate() {
+        if axis.mag() <= EPSILON {
+            continue;
+        }
+        let (min1, max1) = project(instance, axis);
+        let (min2, max2) = project(other, axis);
+        let gap = (min2 - max1).max(min1 - max2);
+        let group = &mut best[(i / 3).min(2)];
+        if gap > group.1 {
+            *group = (i, gap);
         }
-        let overlap = max1.min(max2) - min1.max(min2);
-        if overlap < min_overlap {
-            min_overlap = overlap;
-            mtv = *axis;
-            mtv_index = i;
+    }
+    //same preference for faces as sat(), mirrored since a bigger gap is better here
+    let (mut index, mut max_gap) = best[0];
+    for (i, gap) in &best[1..] {
+        if *gap * SAT_RELATIVE_TOLERANCE - SAT_ABSOLUTE_TOLERANCE > max_gap {
+            (index, max_gap) = (*i, *gap);
+        }
+    }
+    (
+        orient(instance, other, axes[index]),
+        max_gap,
+        collision_type(instance, other, index),
+    )
+}
+
+//3 face axes each, then the 9 edge cross products (zero when edges are parallel)
+fn sat_axes(instance: &Cuboid, other: &Cuboid) -> [Vec3; 15] {
+    let mut axes = [Vec3::default(); 15];
+    axes[..3].copy_from_slice(&instance.face_axes);
+    axes[3..6].copy_from_slice(&other.face_axes);
+    for i in 0..3 {
+        for j in 0..3 {
+            let cross = instance.face_axes[i].cross(&other.face_axes[j]);
+            if let Some(cross) = cross.normalize() {
+                axes[6 + i * 3 + j] = cross;
+            }
         }
     }
+    axes
+}
 
-    let mut scaled_mtv = mtv * min_overlap;
-    if (instance.position - other.position).dot(&scaled_mtv) < 0.0 {
-        scaled_mtv = -scaled_mtv; //other->self
+fn project(instance: &Cuboid, axis: &Vec3) -> (f32, f32) {
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    for corner in instance.corners {
+        let x = corner.dot(axis);
+        min = min.min(x);
+        max = max.max(x);
     }
+    (min, max)
+}
 
-    Some((
-        scaled_mtv,
-        if mtv_index < 6 {
-            CollisionType::Face
-        } else {
-            CollisionType::EdgeEdge(
-                edge_axes[(mtv_index - 6) / 3],
-                edge_axes[(mtv_index - 6) % 3],
-            )
-        },
-    ))
+//flips axis to point other->self
+fn orient(instance: &Cuboid, other: &Cuboid, axis: Vec3) -> Vec3 {
+    if (instance.position - other.position).dot(&axis) < 0.0 {
+        -axis
+    } else {
+        axis
+    }
+}
+
+fn collision_type(instance: &Cuboid, other: &Cuboid, axis_index: usize) -> CollisionType {
+    if axis_index < 6 {
+        CollisionType::Face
+    } else {
+        //directions of the two edges whose cross product picked the axis
+        CollisionType::EdgeEdge(
+            instance.face_axes[(axis_index - 6) / 3],
+            other.face_axes[(axis_index - 6) % 3],
+        )
+    }
 }
 
+//points up to max_gap outside the reference face are kept as speculative (negative depth)
 fn calc_contact_manifold(
     instance: &Cuboid,
     other: &Cuboid,
     collision_normal: Vec3,
-) -> [Option<ContactPoint>; MAX_MANIFOLD_VERTICES] {
+    max_gap: f32,
+) -> [Option<ContactPoint>; MAX_CONTACT_POINTS] {
     //sutherland-hodgman
     //remember collision_normal is other->self
-    let incident_face = most_aligned_with(&other.get_all_face_axes(), &collision_normal);
-    let reference_face = most_aligned_with(&instance.get_all_face_axes(), &-collision_normal);
-    let incident_face_vertices =
-        order_face_vertices(&incident_face, get_face_vertices(&incident_face, other));
-    let reference_face_vertices = order_face_vertices(
-        &reference_face,
-        get_face_vertices(&reference_face, instance),
-    );
-    let reference_face_sides = [
-        (reference_face_vertices[0], reference_face_vertices[1]),
-        (reference_face_vertices[1], reference_face_vertices[2]),
-        (reference_face_vertices[2], reference_face_vertices[3]),
-        (reference_face_vertices[3], reference_face_vertices[0]),
-    ];
-
-    let mut cur_clipped: [Option<Vec3>; MAX_MANIFOLD_VERTICES] = [
-        Some(incident_face_vertices[0]),
-        Some(incident_face_vertices[1]),
-        Some(incident_face_vertices[2]),
-        Some(incident_face_vertices[3]),
-        None,
-        None,
-        None,
-        None,
-    ];
-    let mut cur_clipped_len = 4;
-    for (ref_p1, ref_p2) in reference_face_sides {
+    let instance_faces = face_normals(instance);
+    let other_faces = face_normals(other);
+    let incident_face_index = most_aligned_with(&other_faces, &collision_normal);
+    let reference_face_index = most_aligned_with(&instance_faces, &-collision_normal);
+    let reference_face = instance_faces[reference_face_index];
+    let reference_face_vertices = face_polygon(instance, reference_face_index);
+    let corners = corner_count(other);
+
+    //each point carries the id of the feature that produced it, incident corners to start with
+    let mut cur_clipped = face_polygon(other, incident_face_index);
+    let sides = reference_face_vertices.len();
+    for i in 0..sides {
+        let (side_id, ref_p1) = reference_face_vertices[i];
+        let (_, ref_p2) = reference_face_vertices[(i + 1) % sides];
         let reference_plane = Plane {
             point: ref_p1,
             normal: (ref_p2 - ref_p1)
@@ -130,99 +224,220 @@ fn calc_contact_manifold(
                 .normalize()
                 .unwrap(),
         };
+        let intersect = |(id1, p1): (u32, Vec3), (id2, p2): (u32, Vec3)| {
+            reference_plane
+                .intersect_with_line_segment(&p1, &p2)
+                .map(|x| (clip_feature_id(id1, id2, side_id, corners), x))
+        };
 
-        let mut next_clipped: [Option<Vec3>; MAX_MANIFOLD_VERTICES] = [None; 8];
-        let mut next_clipped_len = 0;
-        for (i, p1) in cur_clipped.iter().enumerate() {
-            let Some(p1) = *p1 else {
-                break;
-            };
-
-            let Some(p2) = cur_clipped[(i + 1) % cur_clipped_len] else {
-                break;
-            };
+        let mut next_clipped = Vec::with_capacity(cur_clipped.len() + 1);
+        for (i, &p1) in cur_clipped.iter().enumerate() {
+            let p2 = cur_clipped[(i + 1) % cur_clipped.len()];
             // <=0.0 bc plane normals point outward
-            let p1_inside = reference_plane.distance_to_point(&p1) <= EPSILON;
-            let p2_inside = reference_plane.distance_to_point(&p2) <= EPSILON;
+            let p1_inside = reference_plane.distance_to_point(&p1.1) <= EPSILON;
+            let p2_inside = reference_plane.distance_to_point(&p2.1) <= EPSILON;
             match (p1_inside, p2_inside) {
-                (true, true) => {
-                    if next_clipped_len < MAX_MANIFOLD_VERTICES {
-                        next_clipped[next_clipped_len] = Some(p2);
-                        next_clipped_len += 1;
-                    }
-                }
-
-                (true, false) => {
-                    if next_clipped_len < MAX_MANIFOLD_VERTICES {
-                        next_clipped[next_clipped_len] =
-                            reference_plane.intersect_with_line_segment(&p1, &p2);
-                        next_clipped_len += 1;
-                    }
-                }
+                (true, true) => next_clipped.push(p2),
+                (true, false) => next_clipped.extend(intersect(p1, p2)),
                 (false, true) => {
-                    if next_clipped_len < MAX_MANIFOLD_VERTICES - 1 {
-                        next_clipped[next_clipped_len] =
-                            reference_plane.intersect_with_line_segment(&p1, &p2);
-                        next_clipped_len += 1;
-                        next_clipped[next_clipped_len] = Some(p2);
-                        next_clipped_len += 1;
-                    }
+                    next_clipped.extend(intersect(p1, p2));
+                    next_clipped.push(p2);
                 }
                 (false, false) => (),
             }
         }
         cur_clipped = next_clipped;
-        cur_clipped_len = next_clipped_len;
     }
 
     let reference_plane = Plane {
         normal: reference_face,
-        //OPTIMIZE: could reuse get_face_vertices() call from before (not sure if worth it)
-        point: get_face_vertices(&reference_face, instance)[0],
+        point: reference_face_vertices[0].1,
     };
-    let mut manifold = [None; MAX_MANIFOLD_VERTICES];
-    let mut manifold_len = 0;
-    for point in cur_clipped.iter().take(cur_clipped_len) {
-        let Some(point) = point else {
-            break;
-        };
-        let depth = reference_plane.distance_to_point(point);
-        if depth < EPSILON {
-            manifold[manifold_len] = Some(ContactPoint {
-                point: *point,
-                depth: depth.abs(),
+    //the same pair of faces can produce the same point ids, so they're part of the id too
+    let face_id = (reference_face_index * other_faces.len() + incident_face_index) as u32;
+    let face_pairs = (instance_faces.len() * other_faces.len()) as u32;
+    let mut manifold = Vec::with_capacity(cur_clipped.len());
+    for (id, point) in cur_clipped {
+        let distance = reference_plane.distance_to_point(&point);
+        if distance < EPSILON.max(max_gap) {
+            manifold.push(ContactPoint {
+                point,
+                depth: if distance < EPSILON {
+                    distance.abs()
+                } else {
+                    -distance
+                },
+                id: id.wrapping_mul(face_pairs).wrapping_add(face_id),
+                ..Default::default()
             });
-            manifold_len += 1;
         }
     }
-    manifold
+    reduce_manifold(&manifold, &reference_face)
+}
+//keeps the deepest point, then whichever points span the most area around it. the solver only
+//needs the outline of the contact patch, and fewer points fight each other less
+fn reduce_manifold(
+    points: &[ContactPoint],
+    normal: &Vec3,
+) -> [Option<ContactPoint>; MAX_CONTACT_POINTS] {
+    let mut ans = [None; MAX_CONTACT_POINTS];
+    if points.len() <= MAX_CONTACT_POINTS {
+        for (slot, point) in ans.iter_mut().zip(points) {
+            *slot = Some(*point);
+        }
+        return ans;
+    }
+    let area = |a: &ContactPoint, b: &ContactPoint, c: &ContactPoint| {
+        (b.point - a.point).cross(&(c.point - a.point)).dot(normal)
+    };
+    let best_by = |score: &dyn Fn(&ContactPoint) -> f32| {
+        *points
+            .iter()
+            .max_by(|a, b| score(a).total_cmp(&score(b)))
+            .unwrap()
+    };
+    let p0 = best_by(&|x| x.depth);
+    let p1 = best_by(&|x| (x.point - p0.point).mag());
+    let p2 = best_by(&|x| area(&p0, &p1, x).abs());
+    //flip the winding so the triangle has positive area, then the fourth point is whichever is
+    //furthest outside any of its edges
+    let (p1, p2) = if area(&p0, &p1, &p2) < 0.0 {
+        (p2, p1)
+    } else {
+        (p1, p2)
+    };
+    let outside = |x: &ContactPoint| {
+        -area(&p0, &p1, x)
+            .min(area(&p1, &p2, x))
+            .min(area(&p2, &p0, x))
+    };
+    let p3 = best_by(&outside);
+    ans[0] = Some(p0);
+    ans[1] = Some(p1);
+    ans[2] = Some(p2);
+    if outside(&p3) > 0.0 {
+        ans[3] = Some(p3);
+    }
+    ans
+}
+//ids below corners are incident corners, so a clipped point's id has to stay clear of them
+fn clip_feature_id(edge_start: u32, edge_end: u32, reference_side: u32, corners: u32) -> u32 {
+    let hash = edge_start.wrapping_mul(0x9e37_79b9)
+        ^ edge_end.rotate_left(16).wrapping_mul(0x85eb_ca6b)
+        ^ reference_side.wrapping_add(1).wrapping_mul(0xc2b2_ae35);
+    hash.max(corners)
 }
-fn order_face_vertices(normal: &Vec3, vertices: [Vec3; 4]) -> [Vec3; 4] {
+fn order_face_vertices(normal: &Vec3, vertices: [(u32, Vec3); 4]) -> [(u32, Vec3); 4] {
     //2d
     let normal = normal.normalize().unwrap();
     let mut centre = Vec3::default();
-    for v in vertices {
+    for (_, v) in vertices {
         centre += v;
     }
     centre /= 4.0;
+    //sort by angle in the face's own basis. comparing cross products pairwise isn't a total
+    //order, so near-ties could come out as a bow tie and clip everything away
+    let u = (vertices[0].1 - centre).normalize().unwrap();
+    let w = normal.cross(&u); //normal is "up" from bird pov
+    let angle = |v: &Vec3| {
+        let v = *v - centre;
+        v.dot(&w).atan2(v.dot(&u))
+    };
     let mut ans = vertices;
-    ans.sort_by(|a, b| {
-        let perpendicular = (*a - centre).cross(&(*b - centre));
-        let res = perpendicular.dot(&normal); //normal is "up" from bird pov
-        if res > 0.0 {
-            std::cmp::Ordering::Less
-        } else {
-            //change order of a,b->b,a
-            std::cmp::Ordering::Greater
-        }
-    });
+    ans.sort_by(|(_, a), (_, b)| angle(a).total_cmp(&angle(b)));
+    //start from the same corner every tick so clipping happens in the same order and the
+    //feature ids come out the same
+    let first = (0..4).min_by_key(|&i| ans[i].0).unwrap();
+    ans.rotate_left(first);
     ans //ccw order from bird pov
 }
-fn get_face_vertices(normal: &Vec3, instance: &Cuboid) -> [Vec3; 4] {
+//edge-on hits only touch at one point, clipping faces against each other would invent more
+fn calc_edge_contact(
+    instance: &Cuboid,
+    other: &Cuboid,
+    collision_normal: Vec3, //other->self
+    depth: f32,
+    instance_edge: &Vec3,
+    other_edge: &Vec3,
+) -> [Option<ContactPoint>; MAX_CONTACT_POINTS] {
+    let (a0, a1) = supporting_edge(instance, instance_edge, &-collision_normal);
+    let (b0, b1) = supporting_edge(other, other_edge, &collision_normal);
+    let (closest1, closest2) = closest_points_on_segments(
+        instance.corners[a0],
+        instance.corners[a1],
+        other.corners[b0],
+        other.corners[b1],
+    );
+    let mut manifold = [None; MAX_CONTACT_POINTS];
+    manifold[0] = Some(ContactPoint {
+        point: (closest1 + closest2) / 2.0,
+        depth,
+        //top bit keeps these apart from face contact ids
+        id: 1 << 31 | ((a0 * 8 + a1) * 64 + b0 * 8 + b1) as u32,
+        ..Default::default()
+    });
+    manifold
+}
+//the edge running along axis that reaches furthest in dir, as corner indices
+fn supporting_edge(instance: &Cuboid, axis: &Vec3, dir: &Vec3) -> (usize, usize) {
+    let alignment = |i: usize| instance.face_axes[i].dot(axis).abs();
+    let axis_index = (0..3)
+        .max_by(|&a, &b| alignment(a).total_cmp(&alignment(b)))
+        .unwrap();
+    let mut best = (0, f32::NEG_INFINITY);
+    for (i, corner) in instance.corners.iter().enumerate() {
+        let distance = corner.dot(dir);
+        if distance > best.1 {
+            best = (i, distance);
+        }
+    }
+    //corners are numbered by the sign along each local axis, x being the highest bit
+    let partner = best.0 ^ (4 >> axis_index);
+    (best.0.min(partner), best.0.max(partner))
+}
+//real-time collision detection 5.1.9, either segment can be a point (spheres are)
+fn closest_points_on_segments(p1: Vec3, q1: Vec3, p2: Vec3, q2: Vec3) -> (Vec3, Vec3) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.dot(&d1);
+    let e = d2.dot(&d2);
+    let f = d2.dot(&r);
+    if a <= EPSILON && e <= EPSILON {
+        return (p1, p2);
+    }
+    if a <= EPSILON {
+        return (p1, p2 + d2 * (f / e).clamp(0.0, 1.0));
+    }
+    let c = d1.dot(&r);
+    if e <= EPSILON {
+        return (p1 + d1 * (-c / a).clamp(0.0, 1.0), p2);
+    }
+    let b = d1.dot(&d2);
+    let denom = a * e - b * b;
+    //parallel edges have no unique answer, any s works
+    let mut s = if denom > EPSILON {
+        ((b * f - c * e) / denom).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut t = (b * s + f) / e;
+    if t < 0.0 {
+        t = 0.0;
+        s = (-c / a).clamp(0.0, 1.0);
+    } else if t > 1.0 {
+        t = 1.0;
+        s = ((b - c) / a).clamp(0.0, 1.0);
+    }
+    (p1 + d1 * s, p2 + d2 * t)
+}
+//paired with the corner's index, which doubles as its feature id
+fn get_face_vertices(normal: &Vec3, instance: &Cuboid) -> [(u32, Vec3); 4] {
     let normal = normal.normalize().unwrap();
-    let mut cube_vertices = instance.corners;
+    let mut cube_vertices: [(u32, Vec3); 8] =
+        std::array::from_fn(|i| (i as u32, instance.corners[i]));
     let centre = instance.position;
-    cube_vertices.sort_by(|a, b| {
+    cube_vertices.sort_by(|(_, a), (_, b)| {
         let distance1 = (*a - centre).dot(&normal);
         let distance2 = (*b - centre).dot(&normal);
         distance2 //bc vertex-centre should point in same general dir as normal (outward)
@@ -237,159 +452,1104 @@ fn get_face_vertices(normal: &Vec3, instance: &Cuboid) -> [Vec3; 4] {
         cube_vertices[3],
     ]
 }
-fn most_aligned_with(vectors: &[Vec3], target: &Vec3) -> Vec3 {
+fn most_aligned_with(vectors: &[Vec3], target: &Vec3) -> usize {
     let target = target.normalize().unwrap();
-    let mut best = (Vec3::default(), f32::NEG_INFINITY);
-    for vector in vectors {
+    let mut best = (0, f32::NEG_INFINITY);
+    for (i, vector) in vectors.iter().enumerate() {
         let dot = vector.normalize().unwrap().dot(&target);
         if dot > best.1 {
-            best.0 = *vector;
+            best.0 = i;
             best.1 = dot;
         }
     }
-    best.0
+    best.0
+}
+//outward, world space. indices are what face_polygon() takes
+fn face_normals(body: &Cuboid) -> Vec<Vec3> {
+    match &body.shape {
+        Shape::ConvexHull(hull) => hull
+            .normals
+            .iter()
+            .map(|x| x.rotate(body.rotation))
+            .collect(),
+        _ => body.get_all_face_axes().to_vec(),
+    }
+}
+//ccw from outside, each corner paired with its feature id
+fn face_polygon(body: &Cuboid, face: usize) -> Vec<(u32, Vec3)> {
+    match &body.shape {
+        Shape::ConvexHull(hull) => hull.faces[face]
+            .iter()
+            .map(|&i| {
+                (
+                    i as u32,
+                    body.position + hull.vertices[i].rotate(body.rotation),
+                )
+            })
+            .collect(),
+        _ => {
+            let normal = body.get_all_face_axes()[face];
+            order_face_vertices(&normal, get_face_vertices(&normal, body)).to_vec()
+        }
+    }
+}
+//corner feature ids run from 0 up to this
+fn corner_count(body: &Cuboid) -> u32 {
+    match &body.shape {
+        Shape::ConvexHull(hull) => hull.vertices.len() as u32,
+        _ => 8,
+    }
+}
+//furthest point along dir, world space. rounded shapes give their core's, the radius is added
+//separately
+fn support(body: &Cuboid, dir: Vec3) -> Vec3 {
+    if let Shape::ConvexHull(hull) = &body.shape {
+        let local = hull.support(&dir.rotate(body.rotation.conj()));
+        return body.position + local.rotate(body.rotation);
+    }
+    if let Some(core) = rounded(body) {
+        return core_support(core, dir);
+    }
+    *body
+        .corners
+        .iter()
+        .max_by(|a, b| a.dot(&dir).total_cmp(&b.dot(&dir)))
+        .unwrap()
+}
+//support() for gjk against near. cuboids are cut down to the part near could reach, which is all
+//that could touch it, so a huge one like a long ramp doesn't swamp gjk's precision
+fn support_near(body: &Cuboid, near: &Cuboid, speculative_distance: f32, dir: Vec3) -> Vec3 {
+    if !matches!(body.shape, Shape::Cuboid) {
+        return support(body, dir);
+    }
+    //twice the radius so pushing out through a cut face is never the shortest way out
+    let reach = 2.0 * near.shape.bounding_radius(near.scale) + speculative_distance;
+    let offset = near.position - body.position;
+    let half = body.scale / 2.0;
+    let mut ans = body.position;
+    for (axis, half) in body.face_axes.iter().zip([half.x, half.y, half.z]) {
+        let centre = offset.dot(axis);
+        let (min, max) = (
+            (centre - reach).clamp(-half, half),
+            (centre + reach).clamp(-half, half),
+        );
+        ans += *axis * if dir.dot(axis) >= 0.0 { max } else { min };
+    }
+    ans
+}
+fn core_support((start, end, _): Core, dir: Vec3) -> Vec3 {
+    if (end - start).dot(&dir) > 0.0 {
+        end
+    } else {
+        start
+    }
+}
+//a and b index into the island's local copy of its bodies, in place of info's slot indices
+pub struct IslandContact<'a> {
+    pub a: usize,
+    pub b: usize,
+    pub info: &'a mut CollisionInfo,
+}
+
+//likewise for a joint's bodies
+pub struct IslandJoint<'a> {
+    pub a: usize,
+    pub b: usize,
+    pub joint: &'a mut Joint,
+}
+
+//precomputed once per tick, the accumulated impulses are written back into the manifold after
+#[derive(Clone, Copy, Default)]
+struct PointConstraint {
+    r1: Vec3,
+    r2: Vec3,
+    normal_mass: f32,
+    tangent_mass: [f32; 2],
+    velocity_bias: f32,
+    normal_impulse: f32,
+    tangent_impulse: [f32; 2],
+}
+
+struct ContactConstraint {
+    normal: Vec3,
+    tangents: [Vec3; 2],
+    material: CombinedMaterial,
+    points: [Option<PointConstraint>; MAX_CONTACT_POINTS],
+}
+
+//joints are solved in the same loop, ahead of the contacts each iteration
+pub fn resolve_collisions(
+    contacts: &mut [IslandContact],
+    joints: &mut [IslandJoint],
+    bodies: &mut [Cuboid],
+    settings: &WorldSettings,
+    iterations: usize,
+) {
+    let dt = settings.dt;
+    let mut constraints = Vec::with_capacity(contacts.len());
+    for IslandContact { a, b, info } in contacts.iter() {
+        let (instance, other) = pair_mut(bodies, *a, *b);
+        let (inv_m1, inv_moi1) = solver_mass(instance);
+        let (inv_m2, inv_moi2) = solver_mass(other);
+        let material = instance.material.combine(&other.material);
+        let normal = info.normal;
+        let tangents = tangent_basis(normal);
+        let mut points = [None; MAX_CONTACT_POINTS];
+        for (constraint, point) in points.iter_mut().zip(&info.manifold) {
+            let Some(point) = point else {
+                continue;
+            };
+            let r1 = point.point - instance.centre_of_mass();
+            let r2 = point.point - other.centre_of_mass();
+            let inverse_effective_mass = |dir: Vec3| {
+                let k = inv_m1
+                    + inv_m2
+                    + (&inv_moi1 * &r1.cross(&dir)).cross(&r1).dot(&dir)
+                    + (&inv_moi2 * &r2.cross(&dir)).cross(&r2).dot(&dir);
+                if k.epsilon_equals(0.0) { 0.0 } else { 1.0 / k }
+            };
+
+            //restitution is measured against the approach speed before any impulses this tick
+            let v_n = relative_velocity(instance, other, r1, r2).dot(&normal);
+            //a speculative contact (negative depth) lets them close the gap this tick but no more
+            let speculative = point.depth < 0.0;
+            let mut velocity_bias = if speculative {
+                point.depth / dt
+            } else {
+                let depth = (point.depth - settings.penetration_tolerance).max(0.0);
+                settings.baumgarte_bias / dt * depth
+            };
+            //and only bounces if the gap really does close this tick
+            if v_n < -settings.restitution_threshold && (!speculative || v_n * dt < point.depth) {
+                let restitution_velo = -v_n * material.restitution;
+                if speculative {
+                    velocity_bias = restitution_velo;
+                } else {
+                    velocity_bias += restitution_velo;
+                }
+            }
+
+            let mut constraint_point = PointConstraint {
+                r1,
+                r2,
+                normal_mass: inverse_effective_mass(normal),
+                tangent_mass: tangents.map(inverse_effective_mass),
+                velocity_bias,
+                ..Default::default()
+            };
+            if settings.warm_starting {
+                constraint_point.normal_impulse = point.normal_impulse;
+                constraint_point.tangent_impulse = tangents.map(|x| point.tangent_impulse.dot(&x));
+            }
+            *constraint = Some(constraint_point);
+        }
+        constraints.push(ContactConstraint {
+            normal,
+            tangents,
+            material,
+            points,
+        });
+    }
+    let mut joint_constraints: Vec<JointConstraint> = joints
+        .iter()
+        .map(|IslandJoint { a, b, joint }| {
+            JointConstraint::new(joint, &bodies[*a], &bodies[*b], settings)
+        })
+        .collect();
+    //only once every approach speed has been measured, otherwise a pile's earlier contacts
+    //make its later ones look like impacts and they bounce
+    if settings.warm_starting {
+        for (IslandJoint { a, b, .. }, constraint) in joints.iter().zip(&joint_constraints) {
+            let (a, b) = pair_mut(bodies, *a, *b);
+            constraint.warm_start(a, b);
+        }
+        for (IslandContact { a, b, .. }, constraint) in contacts.iter().zip(&constraints) {
+            let (instance, other) = pair_mut(bodies, *a, *b);
+            for point in constraint.points.iter().flatten() {
+                let impulse = constraint.normal * point.normal_impulse
+                    + constraint.tangents[0] * point.tangent_impulse[0]
+                    + constraint.tangents[1] * point.tangent_impulse[1];
+                apply_impulse(instance, other, point.r1, point.r2, impulse);
+            }
+        }
+    }
+
+    for _ in 0..iterations {
+        for (IslandJoint { a, b, .. }, constraint) in joints.iter().zip(&mut joint_constraints) {
+            let (a, b) = pair_mut(bodies, *a, *b);
+            constraint.solve(a, b);
+        }
+        for (IslandContact { a, b, .. }, constraint) in contacts.iter().zip(&mut constraints) {
+            let (instance, other) = pair_mut(bodies, *a, *b);
+            let normal = constraint.normal;
+            let tangents = constraint.tangents;
+            for point in constraint.points.iter_mut().flatten() {
+                let (r1, r2) = (point.r1, point.r2);
+                //clamp the running total rather than each increment, so an earlier overshoot can
+                //be taken back
+                let v_n = relative_velocity(instance, other, r1, r2).dot(&normal);
+                let impulse_mag = point.normal_mass * (point.velocity_bias - v_n);
+                let new_impulse = (point.normal_impulse + impulse_mag).max(0.0); //only push, never pull
+                let delta = new_impulse - point.normal_impulse;
+                point.normal_impulse = new_impulse;
+                apply_impulse(instance, other, r1, r2, normal * delta);
+
+                let v_rel = relative_velocity(instance, other, r1, r2);
+                let mut new_impulse = [0.0; 2];
+                for i in 0..2 {
+                    new_impulse[i] =
+                        point.tangent_impulse[i] - point.tangent_mass[i] * v_rel.dot(&tangents[i]);
+                }
+                let tangent_mag = new_impulse[0].hypot(new_impulse[1]);
+                if tangent_mag > constraint.material.static_friction * point.normal_impulse {
+                    //sliding, so kinetic friction takes over
+                    let scale =
+                        constraint.material.dynamic_friction * point.normal_impulse / tangent_mag;
+                    new_impulse = new_impulse.map(|x| x * scale);
+                }
+                let delta = [
+                    new_impulse[0] - point.tangent_impulse[0],
+                    new_impulse[1] - point.tangent_impulse[1],
+                ];
+                point.tangent_impulse = new_impulse;
+                apply_impulse(
+                    instance,
+                    other,
+                    r1,
+                    r2,
+                    tangents[0] * delta[0] + tangents[1] * delta[1],
+                );
+            }
+        }
+    }
+
+    for (IslandJoint { joint, .. }, constraint) in joints.iter_mut().zip(&joint_constraints) {
+        constraint.store(joint);
+    }
+    for (contact, constraint) in contacts.iter_mut().zip(&constraints) {
+        for (point, solved) in contact.info.manifold.iter_mut().zip(&constraint.points) {
+            if let (Some(point), Some(solved)) = (point, solved) {
+                point.normal_impulse = solved.normal_impulse;
+                point.tangent_impulse = constraint.tangents[0] * solved.tangent_impulse[0]
+                    + constraint.tangents[1] * solved.tangent_impulse[1];
+            }
+        }
+    }
+}
+
+pub(crate) fn pair_mut(bodies: &mut [Cuboid], a: usize, b: usize) -> (&mut Cuboid, &mut Cuboid) {
+    if a < b {
+        let (slice1, slice2) = bodies.split_at_mut(b);
+        (&mut slice1[a], &mut slice2[0])
+    } else {
+        let (slice1, slice2) = bodies.split_at_mut(a);
+        (&mut slice2[0], &mut slice1[b])
+    }
+}
+
+//sleeping bodies are static until they're woken at the end of the tick
+pub(crate) fn solver_mass(body: &Cuboid) -> (f32, Mat3) {
+    if body.asleep {
+        (0.0, Mat3::zero())
+    } else {
+        (body.inverse_mass, body.inverse_inertia)
+    }
+}
+
+pub(crate) fn relative_velocity(instance: &Cuboid, other: &Cuboid, r1: Vec3, r2: Vec3) -> Vec3 {
+    (instance.velocity + instance.angular_velocity.cross(&r1))
+        - (other.velocity + other.angular_velocity.cross(&r2))
+}
+
+//impulse acts on instance, other gets the opposite
+pub(crate) fn apply_impulse(
+    instance: &mut Cuboid,
+    other: &mut Cuboid,
+    r1: Vec3,
+    r2: Vec3,
+    impulse: Vec3,
+) {
+    let (inv_m1, inv_moi1) = solver_mass(instance);
+    let (inv_m2, inv_moi2) = solver_mass(other);
+    instance.velocity += impulse * inv_m1;
+    instance.angular_velocity += &inv_moi1 * &(r1.cross(&impulse));
+    other.velocity -= impulse * inv_m2;
+    other.angular_velocity -= &inv_moi2 * &(r2.cross(&impulse));
+}
+
+//angular impulse acts on instance, other gets the opposite
+pub(crate) fn apply_angular_impulse(instance: &mut Cuboid, other: &mut Cuboid, impulse: Vec3) {
+    let (_, inv_moi1) = solver_mass(instance);
+    let (_, inv_moi2) = solver_mass(other);
+    instance.angular_velocity += &inv_moi1 * &impulse;
+    other.angular_velocity -= &inv_moi2 * &impulse;
+}
+
+//any perpendicular pair works as long as the same normal always gives the same pair
+pub(crate) fn tangent_basis(normal: Vec3) -> [Vec3; 2] {
+    let tangent = if normal.x.abs() >= 0.57735 {
+        Vec3 {
+            x: normal.y,
+            y: -normal.x,
+            z: 0.0,
+        }
+    } else {
+        Vec3 {
+            x: 0.0,
+            y: normal.z,
+            z: -normal.y,
+        }
+    }
+    .normalize()
+    .unwrap();
+    [tangent, normal.cross(&tangent)]
+}
+
+//(last time apart, first time touching) as fractions of the way from start to body.position.
+//only the translation is swept, the rotation stays at the end one. None if they never touch or
+//already overlap at start, which the discrete test handles
+pub fn time_of_impact(body: &Cuboid, start: Vec3, other: &Cuboid) -> Option<(f32, f32)> {
+    let displacement = body.position - start;
+    let entry = body
+        .aabb
+        .translate(-displacement)
+        .time_of_entry(displacement, &other.aabb)?;
+    let mut probe = body.clone();
+    let mut overlaps_at = |t: f32| {
+        probe.position = start + displacement * t;
+        probe.update_derived();
+        probe.aabb.intersects(&other.aabb) && overlaps(&probe, other)
+    };
+    if overlaps_at(0.0) {
+        return None;
+    }
+    //steps shorter than either body's thinnest side can't skip over it. static shapes are
+    //surfaces, only the body's own thickness matters
+    let thinnest = if other.shape.is_static() {
+        body.smallest_extent()
+    } else {
+        body.smallest_extent().min(other.smallest_extent())
+    };
+    let step = (thinnest * 0.5 / displacement.mag()).max(1.0 / MAX_CCD_STEPS as f32);
+    let mut apart = 0.0;
+    let mut t = entry;
+    while !overlaps_at(t) {
+        if t >= 1.0 {
+            return None;
+        }
+        apart = t;
+        t = (t + step).min(1.0);
+    }
+    let mut touching = t;
+    for _ in 0..CCD_BISECTIONS {
+        let mid = (apart + touching) / 2.0;
+        if overlaps_at(mid) {
+            touching = mid;
+        } else {
+            apart = mid;
+        }
+    }
+    Some((apart, touching))
+}
+
+//see sat() for separating_axis, pass &mut None to test every axis. pairs that are apart but
+//within speculative_distance get negative depth contacts, pass 0.0 for only touching pairs.
+//meshes, heightfields and compounds can touch at more than one normal, they need
+//detect_collisions()
+pub fn detect_collision(
+    instance: &Cuboid,
+    other: &Cuboid,
+    separating_axis: &mut Option<usize>,
+    speculative_distance: f32,
+) -> Option<CollisionInfo> {
+    if !instance
+        .aabb
+        .expand(speculative_distance)
+        .intersects(&other.aabb)
+        || instance.shape.is_static() && other.shape.is_static()
+        || has_parts(&instance.shape)
+        || has_parts(&other.shape)
+    {
+        return None;
+    }
+    let (normal, depth, collision_type, manifold) = match (rounded(instance), rounded(other)) {
+        _ if other.plane().is_some() => plane_contacts(instance, other, speculative_distance)?,
+        _ if instance.plane().is_some() => {
+            let (normal, depth, collision_type, manifold) =
+                plane_contacts(other, instance, speculative_distance)?;
+            (-normal, depth, collision_type, manifold)
+        }
+        (None, None) => match (&instance.shape, &other.shape) {
+            (Shape::Cuboid, Shape::Cuboid) => {
+                cuboid_cuboid(instance, other, separating_axis, speculative_distance)?
+            }
+            _ => polytope_polytope(instance, other, speculative_distance)?,
+        },
+        (Some(core), Some(other_core)) => rounded_rounded(core, other_core, speculative_distance)?,
+        (Some(core), None) => rounded_polytope(core, other, speculative_distance)?,
+        (None, Some(other_core)) => {
+            let (normal, depth, collision_type, manifold) =
+                rounded_polytope(other_core, instance, speculative_distance)?;
+            (-normal, depth, collision_type, manifold)
+        }
+    };
+    Some(collision_info(
+        instance,
+        other,
+        (normal, depth, collision_type, manifold),
+    ))
+}
+
+//detect_collision() for any pair, adding however many collisions they have to out. against a
+//mesh the body always ends up as instance, and so does a compound against anything but another
+pub fn detect_collisions(
+    instance: &Cuboid,
+    other: &Cuboid,
+    separating_axis: &mut Option<usize>,
+    speculative_distance: f32,
+    out: &mut Vec<CollisionInfo>,
+) {
+    match (has_triangles(&instance.shape), has_triangles(&other.shape)) {
+        _ if instance.shape.is_static() && other.shape.is_static() => (),
+        _ if matches!(instance.shape, Shape::Compound(_)) => {
+            compound_collisions(instance, other, speculative_distance, out)
+        }
+        _ if matches!(other.shape, Shape::Compound(_)) => {
+            compound_collisions(other, instance, speculative_distance, out)
+        }
+        (false, true) => mesh_collisions(instance, other, speculative_distance, out),
+        (true, false) => mesh_collisions(other, instance, speculative_distance, out),
+        _ => out.extend(detect_collision(
+            instance,
+            other,
+            separating_axis,
+            speculative_distance,
+        )),
+    }
+}
+
+fn collision_info(
+    instance: &Cuboid,
+    other: &Cuboid,
+    (normal, depth, collision_type, manifold): NarrowPhase,
+) -> CollisionInfo {
+    CollisionInfo {
+        instance_index: instance.index,
+        other_index: other.index,
+        mtv: normal * depth.max(0.0),
+        normal,
+        collision_type,
+        manifold,
+        parts: (0, 0),
+    }
+}
+
+//each triangle of a mesh or heightfield it touches is a collision of its own, so something
+//resting in a crease is pushed out of both sides
+fn mesh_collisions(
+    body: &Cuboid,
+    mesh_body: &Cuboid,
+    speculative_distance: f32,
+    out: &mut Vec<CollisionInfo>,
+) {
+    let inverse = mesh_body.rotation.conj();
+    let aabb = body.aabb.expand(speculative_distance);
+    let local = AABB::around(
+        [aabb.min.x, aabb.max.x]
+            .into_iter()
+            .flat_map(|x| [aabb.min.y, aabb.max.y].map(|y| (x, y)))
+            .flat_map(|(x, y)| [aabb.min.z, aabb.max.z].map(|z| Vec3 { x, y, z }))
+            .map(|x| (x - mesh_body.position).rotate(inverse)),
+    );
+    let mut scratch = None;
+    for (triangle, corners, face, edge_limits) in static_triangles(&mesh_body.shape, &local) {
+        let corners = corners.map(|x| mesh_body.position + x.rotate(mesh_body.rotation));
+        let face = face.rotate(mesh_body.rotation);
+        //one sided, bodies whose centre has got behind it are left to fall through
+        if (body.position - corners[0]).dot(&face) < 0.0 {
+            continue;
+        }
+        let Some(flat) = triangle_body(&mut scratch, mesh_body, &corners) else {
+            continue;
+        };
+        let Some(mut info) = detect_collision(body, flat, &mut None, speculative_distance) else {
+            continue;
+        };
+        if leans_over_edge(info.normal, face, &corners, edge_limits) {
+            let Some(narrow_phase) = face_contacts(body, flat, face, speculative_distance) else {
+                continue;
+            };
+            info = collision_info(body, mesh_body, narrow_phase);
+        }
+        info.parts = (0, triangle);
+        out.push(info);
+    }
+}
+
+//each child near the other body collides as a body of its own, against whatever the other is
+//made of. its index goes in parts on the compound's side, whichever side that ends up
+fn compound_collisions(
+    compound_body: &Cuboid,
+    other: &Cuboid,
+    speculative_distance: f32,
+    out: &mut Vec<CollisionInfo>,
+) {
+    let aabb = other.aabb.expand(speculative_distance);
+    for (i, child) in compound_body.children.iter().enumerate() {
+        if !child.aabb.intersects(&aabb) {
+            continue;
+        }
+        let start = out.len();
+        detect_collisions(child, other, &mut None, speculative_distance, out);
+        for info in &mut out[start..] {
+            if info.instance_index == compound_body.index {
+                info.parts.0 = i;
+            } else {
+                info.parts.1 = i;
+            }
+        }
+    }
+}
+
+fn has_triangles(shape: &Shape) -> bool {
+    matches!(shape, Shape::TriMesh(_) | Shape::Heightfield(_))
+}
+
+//shapes whose collisions are made of their parts' collisions
+fn has_parts(shape: &Shape) -> bool {
+    has_triangles(shape) || matches!(shape, Shape::Compound(_))
+}
+
+//everything of body's within speculative_distance of the plane's surface touches it, the
+//vertices of polytopes and the ends of rounded shapes' cores. normal is plane->body
+fn plane_contacts(body: &Cuboid, plane: &Cuboid, speculative_distance: f32) -> Option<NarrowPhase> {
+    let (normal, offset) = plane.plane()?;
+    let surface = |x: Vec3| x - normal * (x.dot(&normal) - offset);
+    if let Some((start, end, radius)) = rounded(body) {
+        let ends = if start == end {
+            vec![(start, surface(start))]
+        } else {
+            vec![(start, surface(start)), (end, surface(end))]
+        };
+        return rounded_contacts(normal, radius, &ends, speculative_distance);
+    }
+    let vertices = match &body.shape {
+        Shape::ConvexHull(hull) => hull
+            .vertices
+            .iter()
+            .map(|x| body.position + x.rotate(body.rotation))
+            .collect(),
+        _ => body.corners.to_vec(),
+    };
+    let points: Vec<ContactPoint> = vertices
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, x)| {
+            let depth = offset - x.dot(&normal);
+            (depth >= -speculative_distance).then_some(ContactPoint {
+                point: x + normal * (depth / 2.0),
+                depth,
+                id: i as u32,
+                ..Default::default()
+            })
+        })
+        .collect();
+    let depth = points.iter().map(|x| x.depth).reduce(f32::max)?;
+    Some((
+        normal,
+        depth,
+        CollisionType::Face,
+        reduce_manifold(&points, &normal),
+    ))
+}
+
+//a static shape's triangles whose bounding boxes touch aabb, all in its local space, as
+//(index, corners, normal, edge limits)
+fn static_triangles(shape: &Shape, aabb: &AABB) -> Vec<(usize, [Vec3; 3], Vec3, [f32; 3])> {
+    let mut triangles = Vec::new();
+    match shape {
+        Shape::TriMesh(mesh) => mesh.query(aabb, |x| {
+            triangles.push((x, mesh.corners(x), mesh.normals[x], mesh.edge_limits[x]))
+        }),
+        Shape::Heightfield(field) => field.query(aabb, |x| {
+            triangles.push((x, field.corners(x), field.normals[x], field.edge_limits[x]))
+        }),
+        _ => (),
+    }
+    triangles
+}
+
+//one of a mesh's triangles as a frozen body of its own, which the rest of the narrow phase can
+//treat like any other hull. it keeps the mesh's index, so contacts land on the mesh. scratch
+//holds the last triangle's body, whose hull is reshaped in place rather than rebuilt
+fn triangle_body<'a>(
+    scratch: &'a mut Option<Cuboid>,
+    mesh_body: &Cuboid,
+    corners: &[Vec3; 3],
+) -> Option<&'a Cuboid> {
+    let centre = (corners[0] + corners[1] + corners[2]) / 3.0;
+    let local = corners.map(|x| (x - centre).rotate(mesh_body.rotation.conj()));
+    if let Some(body) = scratch
+        && let Shape::ConvexHull(hull) = &mut body.shape
+        && let Some(hull) = Arc::get_mut(hull)
+    {
+        hull.reshape_flat(local)?;
+        body.position = centre;
+    } else {
+        *scratch = Some(Cuboid {
+            index: mesh_body.index,
+            position: centre,
+            rotation: mesh_body.rotation,
+            shape: Shape::ConvexHull(Arc::new(ConvexHull::flat(local)?)),
+            frozen: true,
+            ..Default::default()
+        });
+    }
+    let body = scratch.as_mut()?;
+    body.update_derived();
+    Some(body)
+}
+
+//whether normal tips out over one of the triangle's edges further than the mesh allows, e.g.
+//when a box sliding off one triangle clips the edge of the next
+fn leans_over_edge(normal: Vec3, face: Vec3, corners: &[Vec3; 3], limits: [f32; 3]) -> bool {
+    let up = normal.dot(&face);
+    (0..3).any(|i| {
+        let outward = (corners[(i + 1) % 3] - corners[i]).cross(&face);
+        let lean = normal.dot(&outward.normalize().unwrap());
+        let too_far = if up > EPSILON {
+            lean / up > limits[i] + EPSILON
+        } else {
+            limits[i] < f32::INFINITY
+        };
+        lean > EPSILON && too_far
+    })
+}
+
+//contacts pushing straight out of a triangle's face, for when the closest way out would catch
+//on one of its edges. normal is face->body
+fn face_contacts(
+    body: &Cuboid,
+    triangle: &Cuboid,
+    face: Vec3,
+    speculative_distance: f32,
+) -> Option<NarrowPhase> {
+    let Some(core) = rounded(body) else {
+        let manifold = calc_contact_manifold(triangle, body, -face, speculative_distance);
+        let depth = manifold
+            .iter()
+            .flatten()
+            .map(|x| x.depth)
+            .reduce(f32::max)?;
+        return Some((face, depth, CollisionType::Face, manifold));
+    };
+    let radius = core.2;
+    if let Some(ends) = lying_flat(core, triangle, face, triangle.position) {
+        return rounded_contacts(face, radius, &ends, speculative_distance);
+    }
+    let deepest = core_support(core, -face);
+    let surface = deepest - face * (deepest - triangle.position).dot(&face);
+    //past the edge it's the neighbour's to push out
+    let polygon = face_polygon(triangle, 0);
+    let inside = (0..3).all(|i| {
+        let (p1, p2) = (polygon[i].1, polygon[(i + 1) % 3].1);
+        (surface - p1).dot(&(p2 - p1).cross(&face)) <= EPSILON
+    });
+    if !inside {
+        return None;
+    }
+    rounded_contacts(face, radius, &[(deepest, surface)], speculative_distance)
+}
+
+type NarrowPhase = (
+    Vec3, //normal, other->self
+    f32,  //depth, negative if apart
+    CollisionType,
+    [Option<ContactPoint>; MAX_CONTACT_POINTS],
+);
+
+fn cuboid_cuboid(
+    instance: &Cuboid,
+    other: &Cuboid,
+    separating_axis: &mut Option<usize>,
+    speculative_distance: f32,
+) -> Option<NarrowPhase> {
+    let (normal, depth, collision_type) = match sat(instance, other, separating_axis) {
+        Some((mtv, collision_type)) => (mtv.normalize()?, mtv.mag(), collision_type),
+        None if speculative_distance > 0.0 => {
+            let (normal, gap, collision_type) = sat_gap(instance, other);
+            if gap > speculative_distance {
+                return None;
+            }
+            (normal, -gap, collision_type)
+        }
+        None => return None,
+    };
+    let manifold = match collision_type {
+        CollisionType::Face => calc_contact_manifold(instance, other, normal, speculative_distance),
+        CollisionType::EdgeEdge(instance_edge, other_edge) => {
+            calc_edge_contact(instance, other, normal, depth, &instance_edge, &other_edge)
+        }
+        CollisionType::Point => unreachable!("sat only finds faces and edges"),
+    };
+    Some((normal, depth, collision_type, manifold))
+}
+
+//rounded shapes are every point within radius of a core segment, which is just a point for
+//spheres. (start, end, radius)
+type Core = (Vec3, Vec3, f32);
+
+fn rounded(body: &Cuboid) -> Option<Core> {
+    match &body.shape {
+        Shape::Cuboid
+        | Shape::ConvexHull(_)
+        | Shape::TriMesh(_)
+        | Shape::Heightfield(_)
+        | Shape::Plane { .. }
+        | Shape::Compound(_) => None,
+        Shape::Sphere { radius } => Some((body.position, body.position, *radius)),
+        Shape::Capsule {
+            radius,
+            half_height,
+        } => {
+            let up = body.face_axes[1] * *half_height;
+            Some((body.position - up, body.position + up, *radius))
+        }
+    }
+}
+
+//hulls against hulls or cuboids. gjk/epa finds the normal, then if that lines up with a face
+//they're clipped like a pair of cuboids, otherwise they only touch at a corner or an edge
+fn polytope_polytope(
+    instance: &Cuboid,
+    other: &Cuboid,
+    speculative_distance: f32,
+) -> Option<NarrowPhase> {
+    let query = gjk::query(
+        |x| support_near(instance, other, speculative_distance, x),
+        |x| support_near(other, instance, speculative_distance, x),
+        instance.position - other.position,
+        speculative_distance,
+    )?;
+    let (normal, depth, on_instance, on_other) = match query {
+        Query::Apart { on_a, on_b } => {
+            let gap = on_a.distance(&on_b);
+            if gap > speculative_distance {
+                return None;
+            }
+            ((on_a - on_b).normalize()?, -gap, on_a, on_b)
+        }
+        Query::Overlapping {
+            normal,
+            depth,
+            on_a,
+            on_b,
+        } => (normal, depth, on_a, on_b),
+    };
+    let instance_faces = face_normals(instance);
+    let other_faces = face_normals(other);
+    let reference = instance_faces[most_aligned_with(&instance_faces, &-normal)];
+    let incident = other_faces[most_aligned_with(&other_faces, &normal)];
+    let (reference_alignment, incident_alignment) =
+        (reference.dot(&-normal), incident.dot(&normal));
+    if reference_alignment.max(incident_alignment) >= FACE_ALIGNMENT {
+        //whichever face lines up best is the reference, and the normal snaps to it so contacts
+        //don't push sideways
+        let (normal, manifold) = if reference_alignment >= incident_alignment {
+            let manifold = calc_contact_manifold(instance, other, -reference, speculative_distance);
+            (-reference, manifold)
+        } else {
+            let manifold = calc_contact_manifold(other, instance, -incident, speculative_distance);
+            (incident, manifold)
+        };
+        if manifold.iter().any(Option::is_some) {
+            return Some((normal, depth, CollisionType::Face, manifold));
+        }
+    }
+    let mut manifold = [None; MAX_CONTACT_POINTS];
+    manifold[0] = Some(ContactPoint {
+        point: (on_instance + on_other) / 2.0,
+        depth,
+        id: 1 << 31, //clear of face contact ids, like edge contacts
+        ..Default::default()
+    });
+    Some((normal, depth, CollisionType::Point, manifold))
+}
+
+//normal is polytope->rounded
+fn rounded_polytope(core: Core, body: &Cuboid, speculative_distance: f32) -> Option<NarrowPhase> {
+    match body.shape {
+        Shape::ConvexHull(_) => rounded_hull(core, body, speculative_distance),
+        _ => rounded_cuboid(core, body, speculative_distance),
+    }
+}
+
+fn rounded_rounded(
+    (start, end, radius): Core,
+    (other_start, other_end, other_radius): Core,
+    speculative_distance: f32,
+) -> Option<NarrowPhase> {
+    let (closest, other_closest) = closest_points_on_segments(start, end, other_start, other_end);
+    let delta = closest - other_closest;
+    if radius + other_radius - delta.mag() < -speculative_distance {
+        return None;
+    }
+    //cores crossing each other, push apart across both
+    let normal = delta
+        .normalize()
+        .or_else(|| (end - start).cross(&(other_end - other_start)).normalize())
+        .unwrap_or(Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        });
+    let surface = |x: Vec3| {
+        let (_, other_closest) = closest_points_on_segments(x, x, other_start, other_end);
+        other_closest + normal * other_radius
+    };
+    //side by side, so touching along where they overlap rather than at one arbitrary point
+    let dir = (end - start).normalize();
+    let other_dir = (other_end - other_start).normalize();
+    if let (Some(dir), Some(other_dir)) = (dir, other_dir)
+        && dir.cross(&other_dir).mag() < FLAT_TOLERANCE
+    {
+        let length = (end - start).mag();
+        let t1 = (other_start - start).dot(&dir) / length;
+        let t2 = (other_end - start).dot(&dir) / length;
+        let (t_min, t_max) = (t1.min(t2).max(0.0), t1.max(t2).min(1.0));
+        if t_max - t_min > EPSILON {
+            let ends = [t_min, t_max].map(|t| {
+                let x = start + (end - start) * t;
+                (x, surface(x))
+            });
+            return rounded_contacts(normal, radius, &ends, speculative_distance);
+        }
+    }
+    rounded_contacts(
+        normal,
+        radius,
+        &[(closest, other_closest + normal * other_radius)],
+        speculative_distance,
+    )
+}
+
+//normal is cuboid->rounded
+fn rounded_cuboid(
+    (start, end, radius): Core,
+    cuboid: &Cuboid,
+    speculative_distance: f32,
+) -> Option<NarrowPhase> {
+    let half = cuboid.scale / 2.0;
+    let half = [half.x, half.y, half.z];
+    let local = |x: Vec3| {
+        let offset = x - cuboid.position;
+        cuboid.face_axes.map(|axis| offset.dot(&axis))
+    };
+    let (local_start, local_end) = (local(start), local(end));
+    let (normal, closest, surface) = if segment_hits_box(local_start, local_end, half) {
+        //core's inside, so push it out through whichever face clears it soonest. the
+        //deepest end decides, it's the last to come out
+        let mut best = (f32::INFINITY, 0, 1.0);
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let deepest = (sign * local_start[axis]).min(sign * local_end[axis]);
+                let push = half[axis] - deepest;
+                if push < best.0 {
+                    best = (push, axis, sign);
+                }
+            }
+        }
+        let (push, axis, sign) = best;
+        let normal = cuboid.face_axes[axis] * sign;
+        let closest = if sign * local_start[axis] <= sign * local_end[axis] {
+            start
+        } else {
+            end
+        };
+        (normal, closest, closest + normal * push)
+    } else {
+        //the closest pair is either one of the core's ends or somewhere along a box edge
+        let mut best = (start, closest_point_on_cuboid(start, cuboid));
+        let mut consider = |(x, on_box): (Vec3, Vec3)| {
+            if x.distance_squared(&on_box) < best.0.distance_squared(&best.1) {
+                best = (x, on_box);
+            }
+        };
+        consider((end, closest_point_on_cuboid(end, cuboid)));
+        if start.distance_squared(&end) > EPSILON * EPSILON {
+            for i in 0..8 {
+                for bit in [1, 2, 4] {
+                    if i & bit == 0 {
+                        consider(closest_points_on_segments(
+                            start,
+                            end,
+                            cuboid.corners[i],
+                            cuboid.corners[i | bit],
+                        ));
+                    }
+                }
+            }
+        }
+        let (closest, on_box) = best;
+        let delta = closest - on_box;
+        if radius - delta.mag() < -speculative_distance {
+            return None;
+        }
+        (delta.normalize()?, closest, on_box)
+    };
+
+    if let Some(ends) = lying_flat((start, end, radius), cuboid, normal, surface) {
+        return rounded_contacts(normal, radius, &ends, speculative_distance);
+    }
+    rounded_contacts(normal, radius, &[(closest, surface)], speculative_distance)
+}
+
+//normal is hull->rounded
+fn rounded_hull(core: Core, hull: &Cuboid, speculative_distance: f32) -> Option<NarrowPhase> {
+    let (start, _, radius) = core;
+    let query = gjk::query(
+        |x| core_support(core, x),
+        |x| support(hull, x),
+        start - hull.position,
+        radius + speculative_distance,
+    )?;
+    let (normal, closest, surface) = match query {
+        Query::Apart { on_a, on_b } => {
+            if radius - on_a.distance(&on_b) < -speculative_distance {
+                return None;
+            }
+            ((on_a - on_b).normalize()?, on_a, on_b)
+        }
+        //core's inside, on_b is where it comes out
+        Query::Overlapping {
+            normal, on_a, on_b, ..
+        } => (normal, on_a, on_b),
+    };
+    if let Some(ends) = lying_flat(core, hull, normal, surface) {
+        return rounded_contacts(normal, radius, &ends, speculative_distance);
+    }
+    rounded_contacts(normal, radius, &[(closest, surface)], speculative_distance)
+}
+
+//lying flat on a face, so it touches along a line. the ends of that line, clipped to the face,
+//are the contacts. surface is any point on the face
+fn lying_flat(
+    (start, end, _): Core,
+    body: &Cuboid,
+    normal: Vec3,
+    surface: Vec3,
+) -> Option<[(Vec3, Vec3); 2]> {
+    let dir = (end - start).normalize()?;
+    if dir.dot(&normal).abs() >= FLAT_TOLERANCE {
+        return None;
+    }
+    let faces = face_normals(body);
+    let face = (0..faces.len()).find(|&i| faces[i].dot(&normal) > 1.0 - EPSILON)?;
+    let polygon = face_polygon(body, face);
+    let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+    for (i, (_, p1)) in polygon.iter().enumerate() {
+        let (_, p2) = polygon[(i + 1) % polygon.len()];
+        let side = (p2 - *p1).cross(&faces[face]); //outward
+        let (a, b) = ((start - *p1).dot(&side), (end - *p1).dot(&side));
+        match (a > 0.0, b > 0.0) {
+            (true, true) => return None,
+            (true, false) => t_min = t_min.max(a / (a - b)),
+            (false, true) => t_max = t_max.min(a / (a - b)),
+            (false, false) => (),
+        }
+    }
+    if t_max - t_min <= EPSILON {
+        return None;
+    }
+    Some([t_min, t_max].map(|t| {
+        let x = start + (end - start) * t;
+        (x, x - normal * (x - surface).dot(&normal))
+    }))
+}
+
+//(point on the core, closest point on the other surface) pairs, each contact sits halfway
+//between the two surfaces. indices double as feature ids
+fn rounded_contacts(
+    normal: Vec3,
+    radius: f32,
+    pairs: &[(Vec3, Vec3)],
+    speculative_distance: f32,
+) -> Option<NarrowPhase> {
+    let mut manifold = [None; MAX_CONTACT_POINTS];
+    let mut max_depth = f32::NEG_INFINITY;
+    for (i, (core, surface)) in pairs.iter().enumerate() {
+        let depth = radius - (*core - *surface).dot(&normal);
+        if depth < -speculative_distance {
+            continue;
+        }
+        max_depth = max_depth.max(depth);
+        manifold[i] = Some(ContactPoint {
+            point: *surface - normal * (depth / 2.0),
+            depth,
+            id: i as u32,
+            ..Default::default()
+        });
+    }
+    if manifold.iter().all(Option::is_none) {
+        return None;
+    }
+    Some((normal, max_depth, CollisionType::Point, manifold))
 }
-pub fn resolve_collisions(collisions: &[CollisionInfo], instances: &mut [Cuboid], dt: f32) {
-    const SOLVER_ITERATIONS: i32 = 8;
-    for _ in 0..SOLVER_ITERATIONS {
-        for info in collisions {
-            let (instance_index, other_index) = (
-                info.instance_index.min(info.other_index),
-                info.instance_index.max(info.other_index),
-            );
-
-            let (slice1, slice2) = instances.split_at_mut(other_index);
-            let instance = &mut slice1[instance_index];
-            let other = &mut slice2[0];
-
-            const RESTITUTION_COEFF: f32 = 0.5;
-            const STATIC_FRICTION_COEFF: f32 = 0.6;
-            const BAUMGARTE_BIAS: f32 = 0.3;
-            const PENETRATION_TOLERANCE: f32 = 0.001;
-            let collision_normal = info.mtv.normalize().unwrap();
-            let inv_m1 = instance.get_inverse_mass();
-            let inv_m2 = other.get_inverse_mass();
-            let inv_moi1 = instance.get_inverse_moment_of_inertia();
-            let inv_moi2 = other.get_inverse_moment_of_inertia();
-
-            for point in &info.manifold {
-                let Some(ContactPoint { point, depth }) = point else {
-                    continue;
-                };
-                let point = *point;
-                let depth = (*depth - PENETRATION_TOLERANCE).max(0.0);
-                let r1 = point - instance.position;
-                let r2 = point - other.position;
-                enum ImpulseType {
-                    Normal,
-                    Tangent(f32),
-                }
-                let apply_impulse = |instance: &mut Cuboid,
-                                     other: &mut Cuboid,
-                                     impulse_type: ImpulseType|
-                 -> Option<f32> {
-                    let v1 = instance.velocity;
-                    let v2 = other.velocity;
-                    let w1 = instance.angular_velocity;
-                    let w2 = other.angular_velocity;
-                    let v_rel = (v1 + w1.cross(&r1)) - (v2 + w2.cross(&r2));
-                    let v_n = v_rel.dot(&collision_normal);
-                    let impulse_dir = match impulse_type {
-                        ImpulseType::Normal => collision_normal,
-                        ImpulseType::Tangent(_) => {
-                            let v_rel_tangent = v_rel - v_n * collision_normal;
-                            v_rel_tangent.normalize()?
-                        }
-                    };
-                    let v_error = match impulse_type {
-                        ImpulseType::Normal => v_n,
-                        ImpulseType::Tangent(_) => v_rel.dot(&impulse_dir),
-                    };
-                    if matches!(impulse_type, ImpulseType::Normal) && v_error > 0.0 {
-                        return None;
-                    }
-
-                    let unit_delta_w1 = &inv_moi1 * &(r1.cross(&impulse_dir));
-                    let unit_delta_v1 = unit_delta_w1.cross(&r1);
-                    let unit_delta_w2 = &inv_moi2 * &(r2.cross(&impulse_dir));
-                    let unit_delta_v2 = unit_delta_w2.cross(&r2);
-
-                    let effective_inv_mass = inv_m1
-                        + inv_m2
-                        + unit_delta_v1.dot(&impulse_dir)
-                        + unit_delta_v2.dot(&impulse_dir);
-                    if effective_inv_mass.epsilon_equals(0.0) {
-                        return None;
-                    }
-
-                    let target_velo = match impulse_type {
-                        ImpulseType::Normal => {
-                            let restitution_velo = -v_error * RESTITUTION_COEFF;
-                            let baumgarte = BAUMGARTE_BIAS / dt * depth;
-                            restitution_velo + baumgarte
-                        }
-                        ImpulseType::Tangent(_) => 0.0,
-                    };
-                    let impulse_mag = (target_velo - v_error) / effective_inv_mass;
-                    let impulse_mag = match impulse_type {
-                        ImpulseType::Normal => impulse_mag.max(0.0), //only push, never pull
-
-                        ImpulseType::Tangent(normal_impulse_mag) => {
-                            let max_friction = STATIC_FRICTION_COEFF * normal_impulse_mag;
-                            impulse_mag.clamp(-max_friction, max_friction)
-                        }
-                    };
-                    let impulse = impulse_mag * impulse_dir;
-                    instance.velocity += impulse * inv_m1;
-                    instance.angular_velocity += &inv_moi1 * &(r1.cross(&impulse));
-                    other.velocity -= impulse * inv_m2;
-                    other.angular_velocity -= &inv_moi2 * &(r2.cross(&impulse));
-                    match impulse_type {
-                        ImpulseType::Normal => Some(impulse_mag),
-                        ImpulseType::Tangent(_) => None,
-                    }
-                };
 
-                let Some(normal_impulse_mag) = apply_impulse(instance, other, ImpulseType::Normal)
-                else {
-                    continue;
-                };
-                apply_impulse(instance, other, ImpulseType::Tangent(normal_impulse_mag));
+//slab test, both ends are in the box's local space
+fn segment_hits_box(start: [f32; 3], end: [f32; 3], half: [f32; 3]) -> bool {
+    let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+    for axis in 0..3 {
+        let d = end[axis] - start[axis];
+        if d.abs() < EPSILON {
+            if start[axis].abs() > half[axis] {
+                return false;
             }
+            continue;
+        }
+        let (t1, t2) = (
+            (-half[axis] - start[axis]) / d,
+            (half[axis] - start[axis]) / d,
+        );
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+        if t_min > t_max {
+            return false;
         }
     }
+    true
 }
 
-pub fn detect_collision(instance: &Cuboid, other: &Cuboid) -> Option<CollisionInfo> {
-    if instance.aabb.intersects(&other.aabb) {
-        let (mtv, collision_type) = sat(instance, other)?;
-        //NOTE:i should handle edgeedge separately, but there's not much of a diff since edge-edge
-        //is rare
-        // match collision_type {
-        //     CollisionType::Face => (), //default sutherland-hodgman logic
-        //     CollisionType::EdgeEdge(instance_edge, other_edge) => {
-        //         //find closest point between these 2 3d vectors
-        //     }
-        // }
-        let manifold = calc_contact_manifold(instance, other, mtv.normalize()?);
-        let collision_info = CollisionInfo {
-            instance_index: instance.index,
-            other_index: other.index,
-            mtv,
-            collision_type,
-            manifold,
-        };
+fn closest_point_on_cuboid(x: Vec3, cuboid: &Cuboid) -> Vec3 {
+    let offset = x - cuboid.position;
+    let half = cuboid.scale / 2.0;
+    let half = [half.x, half.y, half.z];
+    let mut closest = cuboid.position;
+    for (axis, half) in cuboid.face_axes.iter().zip(half) {
+        closest += *axis * offset.dot(axis).clamp(-half, half);
+    }
+    closest
+}
 
-        Some(collision_info)
-    } else {
-        None
+//cheaper than detect_collision() when only yes or no matters
+fn overlaps(instance: &Cuboid, other: &Cuboid) -> bool {
+    match (&instance.shape, &other.shape) {
+        (Shape::Cuboid, Shape::Cuboid) => sat(instance, other, &mut None).is_some(),
+        _ => {
+            let mut collisions = Vec::new();
+            detect_collisions(instance, other, &mut None, 0.0, &mut collisions);
+            collisions.iter().any(|x| x.mtv.mag() > 0.0)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::math::Quaternion;
+    use crate::{
+        compound::{Child, Compound},
+        math::Quaternion,
+    };
 
     use super::*;
     #[test]
@@ -405,7 +1565,7 @@ mod tests {
         };
         c1.update_derived();
         c2.update_derived();
-        assert!(sat(&c1, &c2).is_none());
+        assert!(sat(&c1, &c2, &mut None).is_none());
     }
     #[test]
     fn test_sat_collision_type() {
@@ -421,12 +1581,51 @@ mod tests {
         };
         c1.update_derived();
         c2.update_derived();
-        let res = sat(&c1, &c2).expect("sat was None");
+        let res = sat(&c1, &c2, &mut None).expect("sat was None");
         assert_eq!(res.1, CollisionType::Face);
         assert!(res.0.x.abs() < 1e-5);
         assert!((res.0.y + 0.5).abs() < 1e-5);
         assert!(res.0.z.abs() < 1e-5);
     }
+    #[test]
+    fn test_sat_prefers_reference_face() {
+        //a box resting slightly crooked, the floor's up axis is a hair shallower than its own
+        let mut c1 = Cuboid::default();
+        let mut c2 = Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.999,
+                z: 0.0,
+            },
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 1.0,
+                }
+                .normalize()
+                .unwrap(),
+                0.002,
+            ),
+            ..Default::default()
+        };
+        c1.update_derived();
+        c2.update_derived();
+        let mut separating_axis = None;
+        let (mtv, collision_type) = sat(&c2, &c1, &mut separating_axis).unwrap();
+        assert_eq!(collision_type, CollisionType::Face);
+        assert!((mtv.normalize().unwrap() - c2.face_axes[1]).mag() < 1e-5);
+        assert_eq!(separating_axis, None);
+
+        //once apart, the separating axis is remembered and tried first next time
+        c2.position.y = 1.5;
+        c2.update_derived();
+        assert!(sat(&c2, &c1, &mut separating_axis).is_none());
+        let axis = separating_axis.unwrap();
+        assert!(sat(&c2, &c1, &mut separating_axis).is_none());
+        assert_eq!(separating_axis, Some(axis));
+    }
+
     #[test]
     fn test_contact_manifold_flush_face() {
         let mut c1 = Cuboid::default();
@@ -454,6 +1653,7 @@ mod tests {
                 y: 1.0,
                 z: 0.0,
             },
+            0.0,
         )
         .into_iter()
         .flatten()
@@ -529,6 +1729,7 @@ mod tests {
                 y: -1.0,
                 z: 0.0,
             },
+            0.0,
         )
         .into_iter()
         .flatten()
@@ -536,4 +1737,609 @@ mod tests {
         dbg!(&manifold);
         assert_eq!(manifold.len(), 1);
     }
+
+    #[test]
+    fn test_edge_edge_contact() {
+        //two boxes balanced on their edges, crossed at right angles
+        let mut c1 = Cuboid {
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+                std::f32::consts::FRAC_PI_4,
+            ),
+            ..Default::default()
+        };
+        let mut c2 = Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 1.4,
+                z: 0.0,
+            },
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                std::f32::consts::FRAC_PI_4,
+            ),
+            ..Default::default()
+        };
+        c1.update_derived();
+        c2.update_derived();
+        let info = detect_collision(&c2, &c1, &mut None, 0.0).expect("no collision");
+        assert!(matches!(info.collision_type, CollisionType::EdgeEdge(_, _)));
+        let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+        assert_eq!(manifold.len(), 1);
+        let expected_depth = std::f32::consts::SQRT_2 - 1.4;
+        assert!((manifold[0].depth - expected_depth).abs() < 1e-4);
+        let expected_point = Vec3 {
+            x: 0.0,
+            y: 0.7,
+            z: 0.0,
+        };
+        assert!((manifold[0].point - expected_point).mag() < 1e-3);
+    }
+
+    #[test]
+    fn test_manifold_reduced_to_four() {
+        //turned 45 degrees on top of another box, the overlap is an octagon
+        let mut c1 = Cuboid::default();
+        let mut c2 = Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.99,
+                z: 0.0,
+            },
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 0.0,
+                    y: 1.0,
+                    z: 0.0,
+                },
+                std::f32::consts::FRAC_PI_4,
+            ) * Quaternion::from_angle(
+                &Vec3 {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                0.001, //so one side is deeper
+            ),
+            ..Default::default()
+        };
+        c1.update_derived();
+        c2.update_derived();
+        let info = detect_collision(&c2, &c1, &mut None, 0.0).unwrap();
+        let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+        assert_eq!(manifold.len(), 4);
+        let deepest = manifold.iter().map(|x| x.depth).fold(0.0, f32::max);
+        assert_eq!(manifold[0].depth, deepest);
+        //still covers most of the octagon's ~0.83m^2. the points aren't in winding order, but
+        //for a convex quad each diagonal splits it in two, so the 4 triangles cover it twice
+        let area = |a: Vec3, b: Vec3, c: Vec3| (b - a).cross(&(c - a)).mag() / 2.0;
+        let [a, b, c, d] = [0, 1, 2, 3].map(|i| manifold[i].point);
+        let quad_area = (area(a, b, c) + area(a, b, d) + area(a, c, d) + area(b, c, d)) / 2.0;
+        assert!(quad_area > 0.5);
+    }
+
+    #[test]
+    fn test_speculative_contact() {
+        let mut c1 = Cuboid::default();
+        let mut c2 = Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 1.2,
+                z: 0.0,
+            },
+            ..Default::default()
+        };
+        c1.update_derived();
+        c2.update_derived();
+        assert!(detect_collision(&c2, &c1, &mut None, 0.0).is_none());
+        assert!(detect_collision(&c2, &c1, &mut None, 0.1).is_none());
+        let info = detect_collision(&c2, &c1, &mut None, 0.5).unwrap();
+        assert!((info.normal.y - 1.0).abs() < 1e-5);
+        let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+        assert_eq!(manifold.len(), 4);
+        for point in manifold {
+            assert!((point.depth + 0.2).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_sphere_contacts() {
+        let sphere = |position: Vec3| {
+            let mut x = Cuboid {
+                position,
+                shape: Shape::Sphere { radius: 0.5 },
+                ..Default::default()
+            };
+            x.update_derived();
+            x
+        };
+        let s1 = sphere(Vec3::default());
+        let s2 = sphere(Vec3 {
+            x: 0.9,
+            y: 0.0,
+            z: 0.0,
+        });
+        let info = detect_collision(&s2, &s1, &mut None, 0.0).unwrap();
+        assert_eq!(info.collision_type, CollisionType::Point);
+        assert!((info.normal.x - 1.0).abs() < 1e-5);
+        let point = info.manifold[0].unwrap();
+        assert!((point.depth - 0.1).abs() < 1e-5);
+        assert!((point.point.x - 0.45).abs() < 1e-5);
+
+        //resting on a box's edge, tipped 45 degrees so the edge points up
+        let mut c = Cuboid {
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+                std::f32::consts::FRAC_PI_4,
+            ),
+            ..Default::default()
+        };
+        c.update_derived();
+        let s3 = sphere(Vec3 {
+            x: 0.0,
+            y: std::f32::consts::FRAC_1_SQRT_2 + 0.4,
+            z: 0.0,
+        });
+        let info = detect_collision(&s3, &c, &mut None, 0.0).unwrap();
+        assert!((info.normal.y - 1.0).abs() < 1e-5);
+        assert!((info.manifold[0].unwrap().depth - 0.1).abs() < 1e-5);
+        //same contact seen from the box's side
+        let flipped = detect_collision(&c, &s3, &mut None, 0.0).unwrap();
+        assert!((flipped.normal.y + 1.0).abs() < 1e-5);
+        assert!(
+            (flipped.manifold[0].unwrap().point - info.manifold[0].unwrap().point).mag() < 1e-5
+        );
+
+        //centre inside the box, pushed out through the nearest face
+        let s4 = sphere(Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.3,
+        });
+        let info = detect_collision(&s4, &c, &mut None, 0.0).unwrap();
+        assert!((info.normal.z - 1.0).abs() < 1e-5);
+        assert!((info.manifold[0].unwrap().depth - 0.7).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_capsule_contacts() {
+        let lying = |position: Vec3| {
+            //turned so its core runs along x
+            let mut x = Cuboid {
+                position,
+                rotation: Quaternion::from_angle(
+                    &Vec3 {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 1.0,
+                    },
+                    std::f32::consts::FRAC_PI_2,
+                ),
+                shape: Shape::Capsule {
+                    radius: 0.25,
+                    half_height: 0.5,
+                },
+                ..Default::default()
+            };
+            x.update_derived();
+            x
+        };
+        let mut floor = Cuboid {
+            scale: Vec3 {
+                x: 10.0,
+                y: 1.0,
+                z: 10.0,
+            },
+            ..Default::default()
+        };
+        floor.update_derived();
+
+        //flat on a face touches along its whole length, so one contact under each end
+        let c1 = lying(Vec3 {
+            x: 0.0,
+            y: 0.7,
+            z: 0.0,
+        });
+        let info = detect_collision(&c1, &floor, &mut None, 0.0).unwrap();
+        assert!((info.normal.y - 1.0).abs() < 1e-5);
+        let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+        assert_eq!(manifold.len(), 2);
+        for point in &manifold {
+            assert!((point.depth - 0.05).abs() < 1e-5);
+            assert!((point.point.x.abs() - 0.5).abs() < 1e-5);
+        }
+
+        //hanging off the edge, only the part over the face touches
+        let c2 = lying(Vec3 {
+            x: 5.0,
+            y: 0.7,
+            z: 0.0,
+        });
+        let info = detect_collision(&c2, &floor, &mut None, 0.0).unwrap();
+        let mut xs: Vec<f32> = info.manifold.iter().flatten().map(|x| x.point.x).collect();
+        xs.sort_by(f32::total_cmp);
+        assert_eq!(xs.len(), 2);
+        assert!((xs[0] - 4.5).abs() < 1e-5 && (xs[1] - 5.0).abs() < 1e-5);
+
+        //side by side is two contacts as well, crossed is one
+        let c3 = lying(Vec3 {
+            x: 0.25,
+            y: 0.7,
+            z: 0.45,
+        });
+        let info = detect_collision(&c3, &c1, &mut None, 0.0).unwrap();
+        assert!((info.normal.z - 1.0).abs() < 1e-5);
+        assert_eq!(info.manifold.iter().flatten().count(), 2);
+        let mut c4 = lying(Vec3 {
+            x: 0.0,
+            y: 1.15,
+            z: 0.0,
+        });
+        c4.rotation = Quaternion::from_angle(
+            &Vec3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            std::f32::consts::FRAC_PI_2,
+        );
+        c4.update_derived();
+        let info = detect_collision(&c4, &c1, &mut None, 0.0).unwrap();
+        let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+        assert_eq!(manifold.len(), 1);
+        assert!((manifold[0].depth - 0.05).abs() < 1e-5);
+
+        //a sphere resting on one end
+        let mut ball = Cuboid {
+            position: Vec3 {
+                x: 0.5,
+                y: 1.15,
+                z: 0.0,
+            },
+            shape: Shape::Sphere { radius: 0.25 },
+            ..Default::default()
+        };
+        ball.update_derived();
+        let info = detect_collision(&ball, &c1, &mut None, 0.0).unwrap();
+        assert!((info.normal.y - 1.0).abs() < 1e-5);
+        assert!((info.manifold[0].unwrap().depth - 0.05).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_hull_contacts() {
+        let mut points = Vec::new();
+        for x in [-0.5, 0.5] {
+            for y in [-0.5, 0.5] {
+                for z in [-0.5, 0.5] {
+                    points.push(Vec3 { x, y, z });
+                }
+            }
+        }
+        let cube = std::sync::Arc::new(crate::hull::ConvexHull::new(&points).unwrap());
+        let hull = |position: Vec3, angle: f32| {
+            let mut x = Cuboid {
+                position,
+                rotation: Quaternion::from_angle(
+                    &Vec3 {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 1.0,
+                    },
+                    angle,
+                ),
+                shape: Shape::ConvexHull(cube.clone()),
+                ..Default::default()
+            };
+            x.update_derived();
+            x
+        };
+        let mut floor = Cuboid {
+            scale: Vec3 {
+                x: 10.0,
+                y: 1.0,
+                z: 10.0,
+            },
+            ..Default::default()
+        };
+        floor.update_derived();
+        let above = Vec3 {
+            x: 0.0,
+            y: 0.99,
+            z: 0.0,
+        };
+
+        //face to face is clipped like two cuboids, against a cuboid or another hull
+        let resting = hull(above, 0.0);
+        let base = hull(Vec3::default(), 0.0);
+        for other in [&floor, &base] {
+            let info = detect_collision(&resting, other, &mut None, 0.0).unwrap();
+            assert_eq!(info.collision_type, CollisionType::Face);
+            assert!((info.normal.y - 1.0).abs() < 1e-5);
+            let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+            assert_eq!(manifold.len(), 4);
+            for point in &manifold {
+                assert!((point.depth - 0.01).abs() < 1e-4);
+            }
+        }
+
+        //balanced on an edge it touches along that edge
+        let tilted = hull(
+            Vec3 {
+                x: 0.0,
+                y: 0.5 + 0.5f32.hypot(0.5) - 0.01,
+                z: 0.0,
+            },
+            std::f32::consts::FRAC_PI_4,
+        );
+        let info = detect_collision(&tilted, &floor, &mut None, 0.0).unwrap();
+        assert!((info.normal.y - 1.0).abs() < 1e-5);
+        let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+        assert_eq!(manifold.len(), 2);
+        for point in &manifold {
+            assert!(point.point.x.abs() < 1e-4);
+            assert!((point.depth - 0.01).abs() < 1e-4);
+        }
+
+        //a capsule lying on a hull rests on two contacts like it would on a cuboid
+        let mut capsule = Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.7,
+                z: 0.0,
+            },
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+                std::f32::consts::FRAC_PI_2,
+            ),
+            shape: Shape::Capsule {
+                radius: 0.25,
+                half_height: 0.3,
+            },
+            ..Default::default()
+        };
+        capsule.update_derived();
+        let info = detect_collision(&capsule, &base, &mut None, 0.0).unwrap();
+        assert!((info.normal.y - 1.0).abs() < 1e-5);
+        let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+        assert_eq!(manifold.len(), 2);
+        for point in &manifold {
+            assert!((point.depth - 0.05).abs() < 1e-4);
+            assert!((point.point.x.abs() - 0.3).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_mesh_contacts() {
+        //a 4x4 square of two triangles split along the x = z diagonal, facing up
+        let vertices = [(-2.0, -2.0), (2.0, -2.0), (2.0, 2.0), (-2.0, 2.0)]
+            .map(|(x, z)| Vec3 { x, y: 0.0, z })
+            .to_vec();
+        let mesh = crate::trimesh::TriMesh::new(vertices, &[[0, 2, 1], [0, 3, 2]]).unwrap();
+        let mut mesh = Cuboid {
+            shape: Shape::TriMesh(std::sync::Arc::new(mesh)),
+            frozen: true,
+            ..Default::default()
+        };
+        mesh.update_derived();
+        let body = |y: f32| {
+            let mut x = Cuboid {
+                position: Vec3 { x: 0.0, y, z: 0.0 },
+                ..Default::default()
+            };
+            x.update_derived();
+            x
+        };
+
+        //resting across the diagonal it touches both triangles, straight up, never leaning
+        //over the shared edge
+        let mut collisions = Vec::new();
+        detect_collisions(&body(0.49), &mesh, &mut None, 0.0, &mut collisions);
+        let mut triangles: Vec<usize> = collisions.iter().map(|x| x.parts.1).collect();
+        triangles.sort();
+        assert_eq!(triangles, [0, 1]);
+        for info in &collisions {
+            assert_eq!(info.parts.0, 0);
+            assert!((info.normal.y - 1.0).abs() < 1e-5);
+            for point in info.manifold.iter().flatten() {
+                assert!((point.depth - 0.01).abs() < 1e-4);
+            }
+        }
+
+        //the mesh is one sided, from underneath there's nothing to hit
+        collisions.clear();
+        detect_collisions(&body(-0.49), &mesh, &mut None, 0.0, &mut collisions);
+        assert!(collisions.is_empty());
+        assert!(detect_collision(&body(0.49), &mesh, &mut None, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_plane_contacts() {
+        //a ramp through the origin, tilted 45 degrees about z
+        let mut ramp = Cuboid {
+            shape: Shape::Plane {
+                normal: Vec3 {
+                    x: 1.0,
+                    y: 1.0,
+                    z: 0.0,
+                },
+                offset: 0.0,
+            },
+            frozen: true,
+            ..Default::default()
+        };
+        ramp.update_derived();
+        let up = Vec3 {
+            x: 1.0,
+            y: 1.0,
+            z: 0.0,
+        }
+        .normalize()
+        .unwrap();
+        let rotation = Quaternion::from_angle(
+            &Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            -std::f32::consts::FRAC_PI_4,
+        );
+        let body = |shape: Shape, height: f32| {
+            let mut x = Cuboid {
+                position: up * height,
+                rotation,
+                shape,
+                ..Default::default()
+            };
+            x.update_derived();
+            x
+        };
+
+        //a box lying on it touches at its 4 bottom corners, whichever way round the pair is
+        let resting = body(Shape::Cuboid, 0.49);
+        for (info, sign) in [
+            (
+                detect_collision(&resting, &ramp, &mut None, 0.0).unwrap(),
+                1.0,
+            ),
+            (
+                detect_collision(&ramp, &resting, &mut None, 0.0).unwrap(),
+                -1.0,
+            ),
+        ] {
+            assert!((info.normal - up * sign).mag() < 1e-5);
+            let manifold: Vec<ContactPoint> = info.manifold.into_iter().flatten().collect();
+            assert_eq!(manifold.len(), 4);
+            for point in &manifold {
+                assert!((point.depth - 0.01).abs() < 1e-4);
+            }
+        }
+        assert!(detect_collision(&body(Shape::Cuboid, 0.6), &ramp, &mut None, 0.0).is_none());
+
+        //a capsule lying on it at both ends, a sphere at one point
+        let capsule = Shape::Capsule {
+            radius: 0.25,
+            half_height: 0.5,
+        };
+        let lying = Cuboid {
+            rotation: rotation
+                * Quaternion::from_angle(
+                    &Vec3 {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 1.0,
+                    },
+                    std::f32::consts::FRAC_PI_2,
+                ),
+            ..body(capsule, 0.24)
+        };
+        let mut lying = lying;
+        lying.update_derived();
+        let info = detect_collision(&lying, &ramp, &mut None, 0.0).unwrap();
+        assert_eq!(info.manifold.iter().flatten().count(), 2);
+        let info = detect_collision(
+            &body(Shape::Sphere { radius: 0.5 }, 0.4),
+            &ramp,
+            &mut None,
+            0.0,
+        )
+        .unwrap();
+        assert_eq!(info.manifold.iter().flatten().count(), 1);
+        assert!((info.mtv - up * 0.1).mag() < 1e-4);
+    }
+
+    #[test]
+    fn test_compound_contacts() {
+        //two unit cubes side by side along x, and a box just touching the far one from above
+        let halves = Compound::new(
+            [0.0, 1.0]
+                .map(|x| Child {
+                    position: Vec3 { x, y: 0.0, z: 0.0 },
+                    ..Default::default()
+                })
+                .to_vec(),
+        )
+        .unwrap();
+        let mut compound = Cuboid {
+            index: 1,
+            position: Vec3 {
+                x: 0.5,
+                y: 0.0,
+                z: 0.0,
+            },
+            shape: Shape::Compound(Arc::new(halves)),
+            ..Default::default()
+        };
+        compound.update_derived();
+        let mut resting = Cuboid {
+            index: 2,
+            position: Vec3 {
+                x: 1.2,
+                y: 0.99,
+                z: 0.0,
+            },
+            ..Default::default()
+        };
+        resting.update_derived();
+
+        assert!(detect_collision(&compound, &resting, &mut None, 0.0).is_none());
+        let mut collisions = Vec::new();
+        detect_collisions(&compound, &resting, &mut None, 0.0, &mut collisions);
+        detect_collisions(&resting, &compound, &mut None, 0.0, &mut collisions);
+        assert_eq!(collisions.len(), 2);
+        //only the far child touches, and the compound ends up as instance either way round
+        for info in &collisions {
+            assert_eq!(info.instance_index, 1);
+            assert_eq!(info.parts, (1, 0));
+            assert!((info.mtv.mag() - 0.01).abs() < 1e-4);
+            assert_eq!(info.manifold.iter().flatten().count(), 4);
+        }
+    }
+
+    #[test]
+    fn test_time_of_impact_flat_body() {
+        //zero thickness means a zero step, which mustn't stall the sweep
+        let start = Vec3 {
+            x: 0.9,
+            y: 5.0,
+            z: 0.9,
+        };
+        let mut flat = Cuboid {
+            position: Vec3 {
+                x: 0.9,
+                y: -5.0,
+                z: 0.9,
+            },
+            scale: Vec3 {
+                x: 1.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            ..Default::default()
+        };
+        flat.update_derived();
+        //the aabbs cross but the sphere's surface stays out of the corner
+        let mut sphere = Cuboid {
+            index: 1,
+            shape: Shape::Sphere { radius: 0.5 },
+            ..Default::default()
+        };
+        sphere.update_derived();
+        assert!(time_of_impact(&flat, start, &sphere).is_none());
+    }
 }
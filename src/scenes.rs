@@ -1,30 +1,75 @@
-use std::f32::consts::PI;
+use std::{f32::consts::PI, str::FromStr, sync::Arc};
 
 use crate::{
+    compound::{Child, Compound},
+    heightfield::Heightfield,
+    hull::ConvexHull,
+    joint::JointKind,
     math::{Quaternion, Vec3},
+    shape::Shape,
+    trimesh::TriMesh,
     world::Cuboid,
 };
 
-pub const N: usize = 22 * 22 * 22; //number of cuboids minus floor
-
-//TODO: take in cmd args from cargo run and use that to set N and Scene, then i can easily
-//reproduce scenes with the same command
+pub const DEFAULT_N: usize = 22 * 22 * 22; //number of bodies minus floor
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Scene {
     Grid,
-    SlantedTower, //stacking is visible at low N like 9
+    SlantedTower, //stacking is visible at low n like 9
     Meteor,
     InvertedMeteor,
     Catapult,
     Sticks,
     Platforms,
     Cube,
+    Balls,
+    Rocks,
+    Bowl,
+    Terrain,
+    Hopper,
+    Furniture,
+    Tumblers,
+}
+
+//see World::add_joint()
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneJoint {
+    pub kind: JointKind,
+    pub a: usize,
+    pub b: Option<usize>,
+    pub anchor_a: Vec3,
+    pub anchor_b: Vec3,
+}
+
+impl FromStr for Scene {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grid" => Ok(Scene::Grid),
+            "slanted-tower" => Ok(Scene::SlantedTower),
+            "meteor" => Ok(Scene::Meteor),
+            "inverted-meteor" => Ok(Scene::InvertedMeteor),
+            "catapult" => Ok(Scene::Catapult),
+            "sticks" => Ok(Scene::Sticks),
+            "platforms" => Ok(Scene::Platforms),
+            "cube" => Ok(Scene::Cube),
+            "balls" => Ok(Scene::Balls),
+            "rocks" => Ok(Scene::Rocks),
+            "bowl" => Ok(Scene::Bowl),
+            "terrain" => Ok(Scene::Terrain),
+            "hopper" => Ok(Scene::Hopper),
+            "furniture" => Ok(Scene::Furniture),
+            "tumblers" => Ok(Scene::Tumblers),
+            _ => Err(format!("unknown scene: {s}")),
+        }
+    }
 }
 
 impl Scene {
-    fn gen_grid(instances: &mut Vec<Cuboid>, instance_spacing: f32, scale: Vec3) {
-        let num_cols = N.isqrt();
-        for i in 0..N {
+    fn gen_grid(instances: &mut Vec<Cuboid>, n: usize, instance_spacing: f32, scale: Vec3) {
+        let num_cols = n.isqrt();
+        for i in 0..n {
             let row = i / num_cols;
             let col = i % num_cols;
             let position = Vec3 {
@@ -36,30 +81,34 @@ impl Scene {
             let mut instance = Cuboid {
                 position,
                 scale,
-                index: i,
                 rotation: Quaternion::from_angle(
                     &Vec3 {
                         x: 0.0,
                         y: 0.0,
                         z: 1.0,
                     },
-                    i as f32 * 2.0 / N as f32,
+                    i as f32 * 2.0 / n as f32,
                 ),
                 ..Default::default()
             };
-            instances.push(instance);
             instance.update_derived();
+            instances.push(instance);
         }
     }
     fn gen_meteor(
         instances: &mut Vec<Cuboid>,
+        n: usize,
         little_scale: Vec3,
         big_scale: Vec3,
         inverted: bool,
     ) {
         const INSTANCE_SPACING: f32 = 1.5;
-        let num_cols = (N - 1).isqrt();
-        for i in 0..(N - 1) {
+        //the meteor's one of the n
+        if n == 0 {
+            return;
+        }
+        let num_cols = (n - 1).isqrt();
+        for i in 0..(n - 1) {
             let row = i / num_cols;
             let col = i % num_cols;
             let position = Vec3 {
@@ -75,19 +124,19 @@ impl Scene {
             let mut instance = Cuboid {
                 position,
                 scale: little_scale,
-                index: i,
+                ccd: true, //gets flung by the meteor
                 rotation: Quaternion::from_angle(
                     &Vec3 {
                         x: 0.0,
                         y: 1.0,
                         z: 0.0,
                     },
-                    i as f32 * 0.5 / (N - 1) as f32,
+                    i as f32 * 0.5 / (n - 1) as f32,
                 ),
                 ..Default::default()
             };
-            instances.push(instance);
             instance.update_derived();
+            instances.push(instance);
         }
         let mut meteor = Cuboid {
             position: Vec3 {
@@ -97,17 +146,125 @@ impl Scene {
             },
             density: 5.0,
             scale: big_scale,
-            index: N - 1,
             ..Default::default()
         };
-        instances.push(meteor);
         meteor.update_derived();
+        instances.push(meteor);
+    }
+    //a ramp of frozen tiles with bodies above it, shape picks each one's. the tiles count towards
+    //n like any other scene's static geometry, so it's rounded down to however many bodies fit
+    //alongside the tiles they need
+    fn gen_ramp(instances: &mut Vec<Cuboid>, n: usize, shape: impl Fn(usize) -> Shape) {
+        const INSTANCE_SPACING: f32 = 1.5;
+        const TILE_SIZE: f32 = 4.0; //one big ramp would blow up the grid's cell size
+        let width = |bodies: usize| bodies.isqrt().max(1) as f32 * INSTANCE_SPACING;
+        let tiles_per_side = |bodies: usize| ((width(bodies) + 2.0) / TILE_SIZE).ceil() as usize;
+        let Some(n) = (0..=n)
+            .rev()
+            .find(|&bodies| bodies + tiles_per_side(bodies).pow(2) <= n)
+        else {
+            return;
+        };
+        let num_cols = n.isqrt().max(1);
+        let width = width(n);
+        let centre = Vec3 {
+            x: width / 2.0,
+            y: width / 4.0 + 2.0,
+            z: width / 2.0,
+        };
+        let rotation = Quaternion::from_angle(
+            &Vec3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            0.3,
+        );
+        let num_tiles = tiles_per_side(n);
+        let offset = |i: usize| (i as f32 - (num_tiles - 1) as f32 / 2.0) * TILE_SIZE;
+        for i in 0..num_tiles {
+            for j in 0..num_tiles {
+                let mut tile = Cuboid {
+                    position: centre
+                        + Vec3 {
+                            x: offset(i),
+                            y: 0.0,
+                            z: offset(j),
+                        }
+                        .rotate(rotation),
+                    scale: Vec3 {
+                        x: TILE_SIZE,
+                        y: 1.0,
+                        z: TILE_SIZE,
+                    },
+                    rotation,
+                    frozen: true,
+                    ..Default::default()
+                };
+                tile.update_derived();
+                instances.push(tile);
+            }
+        }
+        for i in 0..n {
+            let row = i / num_cols;
+            let col = i % num_cols;
+            let mut instance = Cuboid {
+                position: Vec3 {
+                    x: row as f32 * INSTANCE_SPACING,
+                    y: width / 2.0 + 6.0,
+                    z: col as f32 * INSTANCE_SPACING,
+                },
+                shape: shape(i),
+                //so they land at all sorts of angles
+                rotation: Quaternion::from_angle(
+                    &Vec3 {
+                        x: 1.0,
+                        y: 0.0,
+                        z: 1.0,
+                    },
+                    i as f32,
+                ),
+                ..Default::default()
+            };
+            instance.update_derived();
+            instances.push(instance);
+        }
     }
-    pub fn populate_scene(&self, instances: &mut Vec<Cuboid>) {
+    //populate_scene() panics on counts the scene can't be built from, so check user input first
+    pub fn check_count(&self, n: usize) -> Result<(), String> {
+        match self {
+            Scene::Catapult if !n.is_multiple_of(4) => Err(format!(
+                "count for catapult must be a multiple of 4, got {n}"
+            )),
+            _ => Ok(()),
+        }
+    }
+    //joints refer to bodies by where they are in instances
+    pub fn populate_scene(
+        &self,
+        instances: &mut Vec<Cuboid>,
+        joints: &mut Vec<SceneJoint>,
+        n: usize,
+    ) {
+        //every scene so far stands on a plane at y=0, an ordinary static body
+        let mut floor = Cuboid {
+            shape: Shape::Plane {
+                normal: Vec3 {
+                    x: 0.0,
+                    y: 1.0,
+                    z: 0.0,
+                },
+                offset: 0.0,
+            },
+            ..Default::default()
+        };
+        floor.update_derived();
+        instances.push(floor);
         match self {
             Scene::Grid => {
                 Self::gen_grid(
                     instances,
+                    n,
                     2.0,
                     Vec3 {
                         x: 1.0,
@@ -118,7 +275,7 @@ impl Scene {
             }
             Scene::SlantedTower => {
                 const INSTANCE_SPACING: f32 = 2.0;
-                for i in 0..N {
+                for i in 0..n {
                     let scale = Vec3 {
                         x: 1.0,
                         y: 1.0,
@@ -141,16 +298,16 @@ impl Scene {
                             },
                             i as f32 * 0.5 / 9.0,
                         ),
-                        index: i,
                         ..Default::default()
                     };
-                    instances.push(instance);
                     instance.update_derived();
+                    instances.push(instance);
                 }
             }
             Scene::Meteor => {
                 Self::gen_meteor(
                     instances,
+                    n,
                     Vec3 {
                         x: 0.25,
                         y: 0.25,
@@ -167,6 +324,7 @@ impl Scene {
             Scene::InvertedMeteor => {
                 Self::gen_meteor(
                     instances,
+                    n,
                     Vec3 {
                         x: 0.25,
                         y: 0.25,
@@ -181,66 +339,82 @@ impl Scene {
                 );
             }
             Scene::Catapult => {
-                assert_eq!(N % 4, 0, "N for catapult must be multiple of 4");
+                assert_eq!(n % 4, 0, "n for catapult must be multiple of 4");
                 const INSTANCE_SPACING: f32 = 2.0;
+                let x_axis = Vec3 {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 0.0,
+                };
+                //the long arm starts down on the ground holding the projectile, and the heavy
+                //block drops onto the short arm
+                let tilt = Quaternion::from_angle(&x_axis, -0.4);
                 let mut i = 0;
-                while i < N / 4 * 4 {
+                while i < n / 4 * 4 {
                     let x_coord = i as f32 * INSTANCE_SPACING;
                     let mut fulcrum = Cuboid {
                         position: Vec3 {
                             x: x_coord,
-                            y: 0.5,
+                            y: 0.75,
                             z: 0.0,
                         },
                         scale: Vec3 {
                             x: 1.0,
-                            y: 1.0,
+                            y: 1.5,
                             z: 0.25,
                         },
                         frozen: true,
-                        index: i,
                         ..Default::default()
                     };
                     fulcrum.update_derived();
                     instances.push(fulcrum);
                     i += 1;
+                    let pivot = Vec3 {
+                        x: x_coord,
+                        y: 1.25,
+                        z: 0.0,
+                    };
+                    let along = |z: f32| Vec3 { x: 0.0, y: 0.0, z }.rotate(tilt);
                     let mut lever = Cuboid {
-                        position: Vec3 {
-                            x: x_coord,
-                            y: 2.0,
-                            z: -0.25,
-                        },
+                        position: pivot + along(-0.5),
                         scale: Vec3 {
                             x: 1.0,
                             y: 0.5,
                             z: 4.0,
                         },
-                        rotation: Quaternion::from_angle(
-                            &Vec3 {
-                                x: 1.0,
-                                y: 0.0,
-                                z: 0.0,
-                            },
-                            PI / 6.0,
-                        ),
-                        index: i,
+                        rotation: tilt,
                         ..Default::default()
                     };
                     i += 1;
                     lever.update_derived();
                     instances.push(lever);
-                    let mut projectile = Cuboid {
-                        position: Vec3 {
-                            x: x_coord,
-                            y: 2.5,
-                            z: -0.25,
+                    //the short arm can swing down until just before it hits the ground
+                    joints.push(SceneJoint {
+                        kind: JointKind::Revolute {
+                            axis: x_axis,
+                            limits: Some((-0.1, 1.1)),
                         },
+                        a: instances.len() - 2,
+                        b: Some(instances.len() - 1),
+                        anchor_a: pivot,
+                        anchor_b: pivot,
+                    });
+                    let mut projectile = Cuboid {
+                        position: pivot
+                            + along(-2.0)
+                            + Vec3 {
+                                x: 0.0,
+                                y: 0.375,
+                                z: 0.0,
+                            }
+                            .rotate(tilt),
                         scale: Vec3 {
                             x: 0.25,
                             y: 0.25,
                             z: 0.25,
                         },
-                        index: i,
+                        rotation: tilt,
+                        ccd: true,
                         ..Default::default()
                     };
                     i += 1;
@@ -250,8 +424,8 @@ impl Scene {
                     let mut heavy = Cuboid {
                         position: Vec3 {
                             x: x_coord,
-                            y: 12.0,
-                            z: 0.9,
+                            y: 4.0,
+                            z: along(1.0).z,
                         },
                         scale: Vec3 {
                             x: 1.0,
@@ -259,7 +433,6 @@ impl Scene {
                             z: 1.0,
                         },
                         density: 3.0,
-                        index: i,
                         ..Default::default()
                     };
                     i += 1;
@@ -270,6 +443,7 @@ impl Scene {
             Scene::Sticks => {
                 Self::gen_grid(
                     instances,
+                    n,
                     4.0,
                     Vec3 {
                         x: 1.0,
@@ -281,6 +455,7 @@ impl Scene {
             Scene::Platforms => {
                 Self::gen_grid(
                     instances,
+                    n,
                     1.5,
                     Vec3 {
                         x: 4.0,
@@ -290,7 +465,7 @@ impl Scene {
                 );
             }
             Scene::Cube => {
-                let len = (N as f32).cbrt() as usize;
+                let len = (n as f32).cbrt() as usize;
                 const INSTANCE_SPACING: f32 = 4.0;
                 for i in 0..len {
                     for j in 0..len {
@@ -307,15 +482,13 @@ impl Scene {
                                     y: 1.0,
                                     z: 1.0,
                                 },
-                                index,
-
                                 rotation: Quaternion::from_angle(
                                     &Vec3 {
                                         x: 0.0,
                                         y: 0.0,
                                         z: 1.0,
                                     },
-                                    index as f32 * 2.0 / N as f32,
+                                    index as f32 * 2.0 / n as f32,
                                 ),
                                 ..Default::default()
                             };
@@ -325,6 +498,418 @@ impl Scene {
                     }
                 }
             }
+            Scene::Balls => {
+                //balls and capsules dropped onto a ramp, they roll off the low end
+                Self::gen_ramp(instances, n, |i| {
+                    if i % 2 == 0 {
+                        Shape::Sphere { radius: 0.5 }
+                    } else {
+                        Shape::Capsule {
+                            radius: 0.3,
+                            half_height: 0.4,
+                        }
+                    }
+                });
+            }
+            Scene::Rocks => {
+                //wedges and rocks tumbling down the same ramp. a handful of hulls shared
+                //between all of them, like a real scene would load
+                let mut hulls = vec![Arc::new(wedge())];
+                hulls.extend((0..4).map(|seed| Arc::new(rock(seed))));
+                Self::gen_ramp(instances, n, |i| {
+                    Shape::ConvexHull(hulls[i % hulls.len()].clone())
+                });
+            }
+            Scene::Bowl => {
+                //boxes, balls and capsules poured into a mesh bowl. they slide down across its
+                //edges and pile up in the middle
+                const INSTANCE_SPACING: f32 = 1.5;
+                let num_cols = n.isqrt().max(1);
+                let width = num_cols as f32 * INSTANCE_SPACING;
+                let radius = width + 2.0;
+                let mut mesh = Cuboid {
+                    position: Vec3 {
+                        x: width / 2.0,
+                        y: 0.0,
+                        z: width / 2.0,
+                    },
+                    shape: Shape::TriMesh(Arc::new(bowl(radius, radius / 6.0))),
+                    ..Default::default()
+                };
+                mesh.update_derived();
+                instances.push(mesh);
+                for i in 0..n.saturating_sub(1) {
+                    let shape = match i % 3 {
+                        0 => Shape::Cuboid,
+                        1 => Shape::Sphere { radius: 0.5 },
+                        _ => Shape::Capsule {
+                            radius: 0.3,
+                            half_height: 0.4,
+                        },
+                    };
+                    let mut instance = Cuboid {
+                        position: Vec3 {
+                            x: (i / num_cols) as f32 * INSTANCE_SPACING,
+                            y: radius / 6.0 + 2.0,
+                            z: (i % num_cols) as f32 * INSTANCE_SPACING,
+                        },
+                        rotation: Quaternion::from_angle(
+                            &Vec3 {
+                                x: 1.0,
+                                y: 0.0,
+                                z: 1.0,
+                            },
+                            i as f32 * 0.3,
+                        ),
+                        shape,
+                        ..Default::default()
+                    };
+                    instance.update_derived();
+                    instances.push(instance);
+                }
+            }
+            Scene::Terrain => {
+                //boxes, balls and capsules dropped on rolling hills, rolling and sliding into the
+                //valleys between them
+                const INSTANCE_SPACING: f32 = 1.5;
+                const CELL_SIZE: f32 = 0.5;
+                let num_cols = n.isqrt().max(1);
+                let width = num_cols as f32 * INSTANCE_SPACING + 8.0;
+                let samples = (width / CELL_SIZE) as usize + 1;
+                let field = hills(samples, CELL_SIZE);
+                let top = field.max.y;
+                let mut terrain = Cuboid {
+                    position: Vec3 {
+                        x: width / 2.0 - 4.0,
+                        y: 0.0,
+                        z: width / 2.0 - 4.0,
+                    },
+                    shape: Shape::Heightfield(Arc::new(field)),
+                    ..Default::default()
+                };
+                terrain.update_derived();
+                instances.push(terrain);
+                for i in 0..n.saturating_sub(1) {
+                    let shape = match i % 3 {
+                        0 => Shape::Cuboid,
+                        1 => Shape::Sphere { radius: 0.5 },
+                        _ => Shape::Capsule {
+                            radius: 0.3,
+                            half_height: 0.4,
+                        },
+                    };
+                    let mut instance = Cuboid {
+                        position: Vec3 {
+                            x: (i / num_cols) as f32 * INSTANCE_SPACING,
+                            y: top + 2.0,
+                            z: (i % num_cols) as f32 * INSTANCE_SPACING,
+                        },
+                        rotation: Quaternion::from_angle(
+                            &Vec3 {
+                                x: 1.0,
+                                y: 0.0,
+                                z: 1.0,
+                            },
+                            i as f32 * 0.3,
+                        ),
+                        shape,
+                        ..Default::default()
+                    };
+                    instance.update_derived();
+                    instances.push(instance);
+                }
+            }
+            Scene::Hopper => {
+                //boxes dropped into a hopper of four sloping plane walls, they slide down them and
+                //pile up in the middle
+                const INSTANCE_SPACING: f32 = 2.0;
+                const DROP_HEIGHT: f32 = 10.0; //gen_grid()'s
+                let count = n.saturating_sub(4);
+                let unit = Vec3 {
+                    x: 1.0,
+                    y: 1.0,
+                    z: 1.0,
+                };
+                Self::gen_grid(instances, count, INSTANCE_SPACING, unit);
+                let num_cols = count.isqrt().max(1);
+                let num_rows = count.div_ceil(num_cols).max(1);
+                let centre = Vec3 {
+                    x: (num_rows - 1) as f32 * INSTANCE_SPACING / 2.0,
+                    y: 0.0,
+                    z: (num_cols - 1) as f32 * INSTANCE_SPACING / 2.0,
+                };
+                //half as wide at the bottom as just outside the grid at the drop height
+                let spread = centre.x.max(centre.z) + 1.0;
+                let bottom = spread / 2.0;
+                let slope = (spread - bottom) / DROP_HEIGHT;
+                for i in 0..4 {
+                    let (sin, cos) = (i as f32 * PI / 2.0).sin_cos();
+                    let inward = Vec3 {
+                        x: -cos,
+                        y: 0.0,
+                        z: -sin,
+                    };
+                    let mut wall = Cuboid {
+                        position: centre - inward * bottom,
+                        shape: Shape::Plane {
+                            normal: inward
+                                + Vec3 {
+                                    x: 0.0,
+                                    y: slope,
+                                    z: 0.0,
+                                },
+                            offset: 0.0,
+                        },
+                        ..Default::default()
+                    };
+                    wall.update_derived();
+                    instances.push(wall);
+                }
+            }
+            Scene::Furniture => {
+                //tables, chairs, l beams and dumbbells, each one rigid body made of several
+                //shapes, tossed onto the floor at all sorts of angles
+                const INSTANCE_SPACING: f32 = 3.0;
+                let pieces = [table(), chair(), l_beam(), dumbbell()].map(Arc::new);
+                let num_cols = n.isqrt().max(1);
+                for i in 0..n {
+                    let mut instance = Cuboid {
+                        position: Vec3 {
+                            x: (i / num_cols) as f32 * INSTANCE_SPACING,
+                            y: 3.0 + (i % 4) as f32,
+                            z: (i % num_cols) as f32 * INSTANCE_SPACING,
+                        },
+                        rotation: Quaternion::from_angle(
+                            &Vec3 {
+                                x: 1.0,
+                                y: 2.0,
+                                z: 0.5,
+                            }
+                            .normalize()
+                            .unwrap(),
+                            i as f32 * 0.7,
+                        ),
+                        shape: Shape::Compound(pieces[i % pieces.len()].clone()),
+                        ..Default::default()
+                    };
+                    instance.update_derived();
+                    instances.push(instance);
+                }
+            }
+            Scene::Tumblers => {
+                //roly poly toys weighted low in their bottom caps, which rock themselves back up
+                //however they land, and sticks weighted at one end like hammers, which tumble
+                //round it
+                const INSTANCE_SPACING: f32 = 2.0;
+                let num_cols = n.isqrt().max(1);
+                for i in 0..n {
+                    let (shape, scale, mass_offset) = if i % 2 == 0 {
+                        let (radius, half_height) = (0.4, 0.3);
+                        (
+                            Shape::Capsule {
+                                radius,
+                                half_height,
+                            },
+                            Vec3::default(),
+                            Vec3 {
+                                x: 0.0,
+                                y: -(half_height + radius / 2.0),
+                                z: 0.0,
+                            },
+                        )
+                    } else {
+                        (
+                            Shape::Cuboid,
+                            Vec3 {
+                                x: 0.15,
+                                y: 1.2,
+                                z: 0.15,
+                            },
+                            Vec3 {
+                                x: 0.0,
+                                y: 0.5,
+                                z: 0.0,
+                            },
+                        )
+                    };
+                    let mut instance = Cuboid {
+                        position: Vec3 {
+                            x: (i / num_cols) as f32 * INSTANCE_SPACING,
+                            y: 2.0 + (i % 3) as f32,
+                            z: (i % num_cols) as f32 * INSTANCE_SPACING,
+                        },
+                        rotation: Quaternion::from_angle(
+                            &Vec3 {
+                                x: 1.0,
+                                y: 0.0,
+                                z: 1.0,
+                            }
+                            .normalize()
+                            .unwrap(),
+                            1.0 + i as f32 * 0.9,
+                        ),
+                        angular_velocity: Vec3 {
+                            x: 0.0,
+                            y: 0.0,
+                            z: 4.0,
+                        },
+                        shape,
+                        scale,
+                        mass_offset,
+                        ..Default::default()
+                    };
+                    instance.update_derived();
+                    instances.push(instance);
+                }
+            }
+        }
+    }
+}
+
+//rings of a paraboloid, depth deep at the rim. ccw from above so the inside is solid
+fn bowl(radius: f32, depth: f32) -> TriMesh {
+    const RINGS: usize = 16;
+    const SECTORS: usize = 48;
+    let mut vertices = vec![Vec3::default()];
+    for i in 1..=RINGS {
+        let t = i as f32 / RINGS as f32;
+        for j in 0..SECTORS {
+            let azimuth = 2.0 * PI * j as f32 / SECTORS as f32;
+            vertices.push(Vec3 {
+                x: azimuth.cos() * radius * t,
+                y: depth * t * t,
+                z: azimuth.sin() * radius * t,
+            });
+        }
+    }
+    let at = |ring: usize, sector: usize| 1 + (ring - 1) * SECTORS + sector % SECTORS;
+    let mut triangles = Vec::new();
+    for j in 0..SECTORS {
+        triangles.push([0, at(1, j + 1), at(1, j)]);
+        for i in 1..RINGS {
+            triangles.push([at(i, j), at(i + 1, j + 1), at(i + 1, j)]);
+            triangles.push([at(i, j), at(i, j + 1), at(i + 1, j + 1)]);
         }
     }
+    TriMesh::new(vertices, &triangles).unwrap()
+}
+
+//a square of gentle sine hills, samples to a side, between 0.5 and 2.5 high so they clear the
+//floor
+fn hills(samples: usize, cell_size: f32) -> Heightfield {
+    let heights = (0..samples)
+        .flat_map(|i| (0..samples).map(move |j| (i, j)))
+        .map(|(i, j)| {
+            let (x, z) = (i as f32 * cell_size, j as f32 * cell_size);
+            1.5 + ((x * 0.35).sin() + (z * 0.3 + 1.0).cos()) / 2.0
+        })
+        .collect();
+    Heightfield::new(heights, samples, samples, cell_size).unwrap()
+}
+
+//a triangular prism, like a doorstop
+fn wedge() -> ConvexHull {
+    let mut points = Vec::new();
+    for z in [-0.5, 0.5] {
+        points.push(Vec3 {
+            x: -0.6,
+            y: -0.3,
+            z,
+        });
+        points.push(Vec3 { x: 0.6, y: -0.3, z });
+        points.push(Vec3 { x: -0.6, y: 0.3, z });
+    }
+    ConvexHull::new(&points).unwrap()
+}
+
+//points scattered over a lumpy ellipsoid. a fixed little hash rather than a random number
+//generator, so every run gets the same rocks
+fn rock(seed: u32) -> ConvexHull {
+    let hash = |i: u32| {
+        let x = (i.wrapping_add(seed.wrapping_mul(7919))).wrapping_mul(0x9e37_79b9);
+        let x = (x ^ x >> 15).wrapping_mul(0x85eb_ca6b);
+        (x ^ x >> 13) as f32 / u32::MAX as f32
+    };
+    let points: Vec<Vec3> = (0..24)
+        .map(|i| {
+            let polar = (1.0 - 2.0 * hash(i * 3)).acos();
+            let azimuth = 2.0 * PI * hash(i * 3 + 1);
+            let radius = 0.35 + 0.2 * hash(i * 3 + 2);
+            Vec3 {
+                x: polar.sin() * azimuth.cos() * radius * 1.2,
+                y: polar.cos() * radius * 0.8,
+                z: polar.sin() * azimuth.sin() * radius,
+            }
+        })
+        .collect();
+    ConvexHull::new(&points).unwrap()
+}
+
+//a box of the given size at the given place, unrotated
+fn block(size: [f32; 3], at: [f32; 3]) -> Child {
+    let [x, y, z] = size;
+    let scale = Vec3 { x, y, z };
+    let [x, y, z] = at;
+    Child {
+        scale,
+        position: Vec3 { x, y, z },
+        ..Default::default()
+    }
+}
+
+//a top on four legs
+fn table() -> Compound {
+    let mut children = vec![block([1.6, 0.1, 1.0], [0.0, 0.75, 0.0])];
+    for (x, z) in [(-0.7, -0.4), (-0.7, 0.4), (0.7, -0.4), (0.7, 0.4)] {
+        children.push(block([0.1, 0.7, 0.1], [x, 0.35, z]));
+    }
+    Compound::new(children).unwrap()
+}
+
+//a seat on four legs with a back on one side
+fn chair() -> Compound {
+    let mut children = vec![
+        block([0.5, 0.08, 0.5], [0.0, 0.45, 0.0]),
+        block([0.5, 0.5, 0.08], [0.0, 0.74, -0.21]),
+    ];
+    for (x, z) in [(-0.21, -0.21), (-0.21, 0.21), (0.21, -0.21), (0.21, 0.21)] {
+        children.push(block([0.08, 0.41, 0.08], [x, 0.205, z]));
+    }
+    Compound::new(children).unwrap()
+}
+
+fn l_beam() -> Compound {
+    Compound::new(vec![
+        block([2.0, 0.25, 0.25], [0.0, 0.0, 0.0]),
+        block([0.25, 1.0, 0.25], [-0.875, 0.625, 0.0]),
+    ])
+    .unwrap()
+}
+
+//a bar with a ball on each end
+fn dumbbell() -> Compound {
+    let along_x = Quaternion::from_angle(
+        &Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        },
+        PI / 2.0,
+    );
+    let mut children = vec![Child {
+        shape: Shape::Capsule {
+            radius: 0.08,
+            half_height: 0.6,
+        },
+        rotation: along_x,
+        ..Default::default()
+    }];
+    for x in [-0.7, 0.7] {
+        children.push(Child {
+            shape: Shape::Sphere { radius: 0.25 },
+            position: Vec3 { x, y: 0.0, z: 0.0 },
+            ..Default::default()
+        });
+    }
+    Compound::new(children).unwrap()
 }
@@ -48,19 +48,6 @@ fn calculate_vertex_output(model: VertexInput, model_matrix: mat4x4<f32>) -> Ver
     return out;
 }
 
-@vertex
-fn vs_static(
-    model: VertexInput
-) -> VertexOutput {
-    let model_matrix = mat4x4<f32>(
-        vec4<f32>(1.0, 0.0, 0.0, 0.0),
-        vec4<f32>(0.0, 1.0, 0.0, 0.0),
-        vec4<f32>(0.0, 0.0, 1.0, 0.0),
-        vec4<f32>(0.0, 0.0, 0.0, 1.0),
-    );
-    return calculate_vertex_output(model, model_matrix);
-}
-
 fn srgb_to_linear(colour: f32) -> f32 {
     return pow((colour + 0.055) / 1.055, 2.4);
 }
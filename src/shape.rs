@@ -0,0 +1,190 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use crate::{
+    compound::Compound,
+    heightfield::Heightfield,
+    hull::ConvexHull,
+    math::{Mat3, Vec3},
+    trimesh::TriMesh,
+};
+
+//what a body collides as. scale only sizes cuboids, the rest carry their own dimensions
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Shape {
+    #[default]
+    Cuboid,
+    Sphere {
+        radius: f32,
+    },
+    //a cylinder along local y capped with hemispheres, half_height doesn't include the caps
+    Capsule {
+        radius: f32,
+        half_height: f32,
+    },
+    //shared, so bodies made from the same hull don't each carry a copy
+    ConvexHull(Arc<ConvexHull>),
+    //level geometry, always frozen
+    TriMesh(Arc<TriMesh>),
+    Heightfield(Arc<Heightfield>), //likewise
+    //the half space of points x with x.dot(normal) <= offset, in local space. infinite, and
+    //likewise frozen
+    Plane {
+        normal: Vec3,
+        offset: f32,
+    },
+    //several of the others moving as one body, see Compound
+    Compound(Arc<Compound>),
+}
+
+impl Shape {
+    //local bounding box, also what the renderer scales its unit mesh by
+    pub fn extents(&self, scale: Vec3) -> Vec3 {
+        let (min, max) = self.bounds(scale);
+        max - min
+    }
+    //local bounding box corners. only hulls and compounds aren't centred on the origin
+    pub fn bounds(&self, scale: Vec3) -> (Vec3, Vec3) {
+        let half = match self {
+            Shape::Cuboid => scale,
+            Shape::Sphere { radius } => Vec3 {
+                x: radius * 2.0,
+                y: radius * 2.0,
+                z: radius * 2.0,
+            },
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => Vec3 {
+                x: radius * 2.0,
+                y: (half_height + radius) * 2.0,
+                z: radius * 2.0,
+            },
+            Shape::ConvexHull(hull) => return (hull.min, hull.max),
+            Shape::TriMesh(mesh) => return (mesh.min, mesh.max),
+            Shape::Heightfield(field) => return (field.min, field.max),
+            Shape::Compound(compound) => return (compound.min, compound.max),
+            //its aabb is worked out from the plane itself, see Cuboid::calc_aabb()
+            Shape::Plane { .. } => Vec3::default(),
+        } / 2.0;
+        (-half, half)
+    }
+    pub fn volume(&self, scale: Vec3) -> f32 {
+        match self {
+            Shape::Cuboid => scale.x * scale.y * scale.z,
+            Shape::Sphere { radius } => 4.0 / 3.0 * PI * radius.powi(3),
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => PI * radius * radius * (half_height * 2.0 + 4.0 / 3.0 * radius),
+            Shape::ConvexHull(hull) => hull.volume,
+            Shape::Compound(compound) => compound.volume,
+            Shape::TriMesh(_) | Shape::Heightfield(_) | Shape::Plane { .. } => 0.0,
+        }
+    }
+    //local inertia tensor of a solid body of mass 1
+    pub fn unit_inertia(&self, scale: Vec3) -> Mat3 {
+        let principal = match self {
+            Shape::Cuboid => {
+                let Vec3 { x, y, z } = scale;
+                Vec3 {
+                    x: (1.0 / 12.0) * (y * y + z * z),
+                    y: (1.0 / 12.0) * (x * x + z * z),
+                    z: (1.0 / 12.0) * (x * x + y * y),
+                }
+            }
+            Shape::Sphere { radius } => {
+                let i = 2.0 / 5.0 * radius * radius;
+                Vec3 { x: i, y: i, z: i }
+            }
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => {
+                //cylinder plus the two caps moved out to its ends, split by volume
+                let (r, h) = (*radius, half_height * 2.0);
+                let cylinder = h / (h + 4.0 / 3.0 * r);
+                let caps = 1.0 - cylinder;
+                let side = cylinder * (h * h / 12.0 + r * r / 4.0)
+                    + caps * (2.0 / 5.0 * r * r + h * h / 4.0 + 3.0 / 8.0 * h * r);
+                Vec3 {
+                    x: side,
+                    y: cylinder * r * r / 2.0 + caps * 2.0 / 5.0 * r * r,
+                    z: side,
+                }
+            }
+            Shape::ConvexHull(hull) => return hull.unit_inertia,
+            Shape::Compound(compound) => return compound.unit_inertia,
+            Shape::TriMesh(_) | Shape::Heightfield(_) | Shape::Plane { .. } => {
+                return Mat3::zero();
+            }
+        };
+        Mat3::diagonal(principal)
+    }
+    //furthest any point gets from the centre
+    pub fn bounding_radius(&self, scale: Vec3) -> f32 {
+        match self {
+            Shape::Cuboid => scale.mag() / 2.0,
+            Shape::Sphere { radius } => *radius,
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => radius + half_height,
+            Shape::ConvexHull(hull) => hull.bounding_radius,
+            Shape::TriMesh(mesh) => mesh.bounding_radius,
+            Shape::Heightfield(field) => field.bounding_radius,
+            Shape::Compound(compound) => compound.bounding_radius,
+            Shape::Plane { .. } => f32::INFINITY,
+        }
+    }
+    //too big to go in the hash grid, so tested against every body instead, and never moves
+    pub fn is_static(&self) -> bool {
+        matches!(
+            self,
+            Shape::TriMesh(_) | Shape::Heightfield(_) | Shape::Plane { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sphere_mass_properties() {
+        let sphere = Shape::Sphere { radius: 0.5 };
+        let scale = Vec3::default(); //ignored
+        assert!((sphere.volume(scale) - PI / 6.0).abs() < 1e-6);
+        assert!((sphere.unit_inertia(scale).array[4] - 0.1).abs() < 1e-6);
+        //a sphere is a bit more than half the cube it fits in
+        let cube = Shape::Cuboid.volume(sphere.extents(scale));
+        assert!((sphere.volume(scale) / cube - PI / 6.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_capsule_mass_properties() {
+        let scale = Vec3::default();
+        //no cylinder left, it's just a sphere
+        let short = Shape::Capsule {
+            radius: 0.5,
+            half_height: 0.0,
+        };
+        let sphere = Shape::Sphere { radius: 0.5 };
+        assert!((short.volume(scale) - sphere.volume(scale)).abs() < 1e-6);
+        let (short, sphere) = (short.unit_inertia(scale), sphere.unit_inertia(scale));
+        assert!(
+            short
+                .array
+                .iter()
+                .zip(sphere.array)
+                .all(|(a, b)| (a - b).abs() < 1e-6)
+        );
+        //long and thin, so about a rod's
+        let rod = Shape::Capsule {
+            radius: 0.001,
+            half_height: 0.5,
+        };
+        assert!((rod.unit_inertia(scale).array[0] - 1.0 / 12.0).abs() < 1e-3);
+        assert!(rod.unit_inertia(scale).array[4] < 1e-6);
+        assert_eq!(rod.extents(scale).y, 1.002);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{collections::VecDeque, time::Duration};
+
+//filled in by World::update() every tick
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StepStats {
+    pub integrate_time: Duration,
+    pub grid_init_time: Duration,
+    pub ccd_time: Duration,
+    pub static_time: Duration, //against planes, meshes and heightfields
+    pub broad_time: Duration,  //excludes narrow_time
+    pub narrow_time: Duration,
+    pub impulse_time: Duration,
+    pub total_time: Duration,
+    pub pair_checks: usize,
+    pub collisions: usize, //colliding pairs
+    pub contacts: usize,   //contact points across all manifolds
+    pub bodies_awake: usize,
+    pub islands: usize,           //only ones with contacts or joints
+    pub solver_iterations: usize, //of the island that needed the most
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub min: f64,
+    pub mean: f64,
+    pub p99: f64,
+}
+
+//rolling window over the last `capacity` steps
+#[derive(Debug)]
+pub struct StepStatsWindow {
+    samples: VecDeque<StepStats>,
+    capacity: usize,
+}
+
+impl StepStatsWindow {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "StepStatsWindow capacity must be nonzero");
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, stats: StepStats) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(stats);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    //e.g. window.summary(|x| x.narrow_time.as_secs_f64() * 1000.0)
+    pub fn summary<F>(&self, f: F) -> Option<Summary>
+    where
+        F: Fn(&StepStats) -> f64,
+    {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut values: Vec<f64> = self.samples.iter().map(f).collect();
+        values.sort_by(|a, b| a.total_cmp(b));
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        //nearest rank
+        let p99_rank = ((values.len() as f64 * 0.99).ceil() as usize).max(1);
+        Some(Summary {
+            min: values[0],
+            mean,
+            p99: values[p99_rank - 1],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_summary() {
+        let mut window = StepStatsWindow::new(100);
+        assert!(window.summary(|x| x.pair_checks as f64).is_none());
+        //first 50 get evicted
+        for i in 0..150 {
+            window.push(StepStats {
+                pair_checks: i,
+                ..Default::default()
+            });
+        }
+        assert_eq!(window.len(), 100);
+        let summary = window.summary(|x| x.pair_checks as f64).unwrap();
+        assert_eq!(summary.min, 50.0);
+        assert_eq!(summary.mean, 99.5);
+        assert_eq!(summary.p99, 148.0);
+    }
+}
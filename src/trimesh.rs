@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+
+use crate::{
+    math::{EPSILON, Vec3},
+    world::AABB,
+};
+
+const LEAF_SIZE: usize = 4; //triangles per bvh leaf
+
+//static level geometry in its body's local space. triangles are one sided, they only push
+//bodies out the side they're ccw from
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriMesh {
+    pub vertices: Vec<Vec3>,
+    pub triangles: Vec<[usize; 3]>, //into vertices, in bvh order
+    pub normals: Vec<Vec3>,         //one per triangle
+    //per edge (0->1, 1->2, 2->0), how far a contact normal can lean out over it, as the tangent
+    //of its angle from the face normal. 0 where a flat or concave neighbour carries on, which
+    //would otherwise catch bodies sliding across, and infinite on the mesh's boundary
+    pub edge_limits: Vec<[f32; 3]>,
+    pub min: Vec3, //local bounding box
+    pub max: Vec3,
+    pub bounding_radius: f32,
+    nodes: Vec<Node>,
+}
+
+//bounding volume hierarchy, flattened depth first so a node's left child comes right after it
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    aabb: AABB,
+    start: usize, //first triangle for leaves, the right child otherwise
+    count: usize, //0 for everything but leaves
+}
+
+impl TriMesh {
+    //triangles that share an edge should share its vertices, otherwise it counts as a boundary.
+    //zero area triangles are dropped. None if there's nothing left, or a triangle refers to a
+    //vertex that isn't there
+    pub fn new(vertices: Vec<Vec3>, triangles: &[[usize; 3]]) -> Option<Self> {
+        if triangles.iter().flatten().any(|&i| i >= vertices.len()) {
+            return None;
+        }
+        let mut items: Vec<([usize; 3], Vec3, AABB)> = triangles
+            .iter()
+            .filter(|x| {
+                let [a, b, c] = x.map(|i| vertices[i]);
+                (b - a).cross(&(c - a)).normalize().is_some()
+            })
+            .map(|&x| {
+                let corners = x.map(|i| vertices[i]);
+                let centre = (corners[0] + corners[1] + corners[2]) / 3.0;
+                (x, centre, AABB::around(corners))
+            })
+            .collect();
+        if items.is_empty() {
+            return None;
+        }
+        let mut nodes = Vec::with_capacity(items.len() / LEAF_SIZE * 2 + 1);
+        build(&mut nodes, &mut items, 0);
+        let triangles: Vec<[usize; 3]> = items.into_iter().map(|x| x.0).collect();
+        let normals: Vec<Vec3> = triangles
+            .iter()
+            .map(|x| {
+                let [a, b, c] = x.map(|i| vertices[i]);
+                (b - a).cross(&(c - a)).normalize().unwrap()
+            })
+            .collect();
+
+        let mut edges = HashMap::new();
+        for (i, triangle) in triangles.iter().enumerate() {
+            for j in 0..3 {
+                let (a, b) = (triangle[j], triangle[(j + 1) % 3]);
+                edges
+                    .entry((a.min(b), a.max(b)))
+                    .or_insert(Vec::new())
+                    .push(i);
+            }
+        }
+        let edge_limits = triangles
+            .iter()
+            .enumerate()
+            .map(|(i, triangle)| {
+                std::array::from_fn(|j| {
+                    let (a, b) = (triangle[j], triangle[(j + 1) % 3]);
+                    let neighbour = edges[&(a.min(b), a.max(b))].iter().find(|&&x| x != i);
+                    let Some(&neighbour) = neighbour else {
+                        return f32::INFINITY;
+                    };
+                    edge_limit(normals[i], normals[neighbour], vertices[a], vertices[b])
+                })
+            })
+            .collect();
+
+        let bounds = AABB::around(vertices.iter().copied());
+        Some(Self {
+            bounding_radius: vertices.iter().map(Vec3::mag).fold(0.0, f32::max),
+            vertices,
+            triangles,
+            normals,
+            edge_limits,
+            min: bounds.min,
+            max: bounds.max,
+            nodes,
+        })
+    }
+    //every triangle whose bounding box touches aabb, which is in the mesh's local space
+    pub fn query(&self, aabb: &AABB, mut f: impl FnMut(usize)) {
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.aabb.intersects(aabb) {
+                continue;
+            }
+            if node.count > 0 {
+                (node.start..node.start + node.count)
+                    .filter(|&x| AABB::around(self.corners(x)).intersects(aabb))
+                    .for_each(&mut f);
+            } else {
+                stack.extend([node.start, index + 1]);
+            }
+        }
+    }
+    pub fn corners(&self, triangle: usize) -> [Vec3; 3] {
+        self.triangles[triangle].map(|i| self.vertices[i])
+    }
+}
+
+//how far a normal can lean out over the edge a->b of a triangle facing normal, given the one on
+//the other side faces neighbour
+pub(crate) fn edge_limit(normal: Vec3, neighbour: Vec3, a: Vec3, b: Vec3) -> f32 {
+    let outward = (b - a).cross(&normal).normalize().unwrap();
+    let (lean, up) = (neighbour.dot(&outward), neighbour.dot(&normal));
+    //folded back past a right angle, anything goes
+    if up < EPSILON {
+        f32::INFINITY
+    } else {
+        (lean / up).max(0.0)
+    }
+}
+
+//splits at the median centre along whichever axis they're most spread out on, returns the
+//node's index
+fn build(nodes: &mut Vec<Node>, items: &mut [([usize; 3], Vec3, AABB)], start: usize) -> usize {
+    let index = nodes.len();
+    let aabb = items
+        .iter()
+        .map(|x| x.2)
+        .reduce(|a, b| a.union(&b))
+        .unwrap();
+    nodes.push(Node {
+        aabb,
+        start,
+        count: items.len(),
+    });
+    if items.len() <= LEAF_SIZE {
+        return index;
+    }
+    let spread = AABB::around(items.iter().map(|x| x.1)).get_dimensions();
+    let spread = [spread.x, spread.y, spread.z];
+    let axis = (0..3)
+        .max_by(|&a, &b| spread[a].total_cmp(&spread[b]))
+        .unwrap();
+    let component = |v: &Vec3| [v.x, v.y, v.z][axis];
+    let middle = items.len() / 2;
+    items.select_nth_unstable_by(middle, |a, b| component(&a.1).total_cmp(&component(&b.1)));
+    let (left, right) = items.split_at_mut(middle);
+    build(nodes, left, start);
+    let right = build(nodes, right, start + middle);
+    nodes[index].start = right;
+    nodes[index].count = 0;
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a flat square of side n in the xz plane, two triangles per unit cell, facing up
+    fn grid(n: usize) -> TriMesh {
+        let mut vertices = Vec::new();
+        for i in 0..=n {
+            for j in 0..=n {
+                vertices.push(Vec3 {
+                    x: i as f32,
+                    y: 0.0,
+                    z: j as f32,
+                });
+            }
+        }
+        let at = |i: usize, j: usize| i * (n + 1) + j;
+        let mut triangles = Vec::new();
+        for i in 0..n {
+            for j in 0..n {
+                triangles.push([at(i, j), at(i, j + 1), at(i + 1, j + 1)]);
+                triangles.push([at(i, j), at(i + 1, j + 1), at(i + 1, j)]);
+            }
+        }
+        TriMesh::new(vertices, &triangles).unwrap()
+    }
+
+    #[test]
+    fn test_bvh_query() {
+        let mesh = grid(16);
+        assert!(mesh.normals.iter().all(|x| x.y > 1.0 - EPSILON));
+        let aabb = AABB::new(
+            Vec3 {
+                x: 3.2,
+                y: -0.1,
+                z: 7.5,
+            },
+            Vec3 {
+                x: 4.6,
+                y: 0.1,
+                z: 8.4,
+            },
+        );
+        let mut found = Vec::new();
+        mesh.query(&aabb, |x| found.push(x));
+        found.sort();
+        let expected: Vec<usize> = (0..mesh.triangles.len())
+            .filter(|&x| AABB::around(mesh.corners(x)).intersects(&aabb))
+            .collect();
+        assert_eq!(found, expected);
+        assert_eq!(found.len(), 8); //2x2 cells
+
+        let vertices = mesh.corners(0).to_vec();
+        assert!(TriMesh::new(vertices.clone(), &[[0, 1, 2]]).is_some());
+        assert!(TriMesh::new(vertices, &[[0, 1, 2], [0, 2, 3]]).is_none());
+    }
+
+    #[test]
+    fn test_edge_limits() {
+        let mesh = grid(2);
+        //the outside edges are the boundary, everything inside is flat
+        for (triangle, limits) in mesh.triangles.iter().zip(&mesh.edge_limits) {
+            for (j, limit) in limits.iter().enumerate() {
+                let [a, b] = [triangle[j], triangle[(j + 1) % 3]].map(|i| mesh.vertices[i]);
+                let boundary = [a, b].iter().all(|v| v.x == 0.0)
+                    || [a, b].iter().all(|v| v.x == 2.0)
+                    || [a, b].iter().all(|v| v.z == 0.0)
+                    || [a, b].iter().all(|v| v.z == 2.0);
+                assert_eq!(*limit, if boundary { f32::INFINITY } else { 0.0 });
+            }
+        }
+        //a roof, the ridge is convex so normals can lean over it as far as the other side's
+        let vertices = vec![
+            Vec3 {
+                x: -2.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 1.0,
+            },
+            Vec3 {
+                x: 2.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        ];
+        let roof = TriMesh::new(vertices, &[[0, 2, 1], [1, 2, 3]]).unwrap();
+        let ridge = |t: usize| {
+            let j = (0..3)
+                .find(|&j| {
+                    let (a, b) = (roof.triangles[t][j], roof.triangles[t][(j + 1) % 3]);
+                    a.min(b) == 1 && a.max(b) == 2
+                })
+                .unwrap();
+            roof.edge_limits[t][j]
+        };
+        //the sides' normals are atan(4/3) apart
+        assert!((ridge(0) - 4.0 / 3.0).abs() < 1e-5);
+        assert!((ridge(1) - 4.0 / 3.0).abs() < 1e-5);
+    }
+}
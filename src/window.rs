@@ -1,8 +1,20 @@
 use crate::camera::{Camera, CameraController};
-use crate::math::{Mat4, Vec3};
-use crate::scenes::N;
-use crate::world::{Cuboid, CuboidRaw, PHYSICS_DT, World};
-use crate::{CUBE_INDICES, CUBE_VERTICES, FLOOR_VERTICES, Vertex};
+use crate::heightfield::Heightfield;
+use crate::hull::ConvexHull;
+use crate::math::{Mat4, Quaternion, Vec3};
+use crate::scenes::Scene;
+use crate::shape::Shape;
+use crate::stats::{StepStats, StepStatsWindow};
+use crate::trimesh::TriMesh;
+use crate::world::{Cuboid, CuboidRaw, World, WorldSettings};
+use crate::{
+    CUBE_INDICES, CUBE_VERTICES, PLANE_INDICES, PLANE_VERTICES, Vertex, cylinder_mesh,
+    heightfield_mesh, hull_mesh, sphere_mesh, trimesh_mesh,
+};
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+use std::io::{self, Write};
+use std::ops::Range;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use wgpu::util::DeviceExt;
@@ -16,6 +28,8 @@ use winit::{
     window::{Window, WindowId},
 };
 
+const PLANE_SIZE: f32 = 1000.0; //planes are infinite, this is how far out they're drawn
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct CameraUniform {
@@ -37,6 +51,82 @@ impl CameraUniform {
         self.view_proj = (camera.calc_projection_matrix() * camera.calc_view_matrix()).array;
     }
 }
+struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+    index_format: wgpu::IndexFormat,
+}
+impl Mesh {
+    fn new(device: &wgpu::Device, label: &str, vertices: &[Vertex], indices: &[u16]) -> Self {
+        Self::with_indices(device, label, vertices, indices, wgpu::IndexFormat::Uint16)
+    }
+    //index_format has to match T
+    fn with_indices<T: bytemuck::Pod>(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[Vertex],
+        indices: &[T],
+        index_format: wgpu::IndexFormat,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Vertex Buffer")),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Index Buffer")),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            index_format,
+        }
+    }
+}
+//gpu meshes for shapes shared between bodies, one per Arc. holding the Arc means its address
+//can't be reused by another shape while the entry's around
+struct MeshCache<T> {
+    meshes: HashMap<*const T, (Arc<T>, Mesh)>,
+}
+impl<T> MeshCache<T> {
+    fn new() -> Self {
+        Self {
+            meshes: HashMap::new(),
+        }
+    }
+    //panics if it hasn't been built yet
+    fn get(&self, shape: &Arc<T>) -> &Mesh {
+        &self.meshes[&Arc::as_ptr(shape)].1
+    }
+    fn get_or_build(&mut self, shape: &Arc<T>, build: impl FnOnce(&T) -> Mesh) {
+        self.meshes
+            .entry(Arc::as_ptr(shape))
+            .or_insert_with(|| (shape.clone(), build(shape)));
+    }
+    //drops the meshes, buffers and all, of shapes that aren't drawn any more, e.g. the last body
+    //using them was removed. they're rebuilt if they come back
+    fn retain<'a>(&mut self, drawn: impl Iterator<Item = &'a Arc<T>>)
+    where
+        T: 'a,
+    {
+        let drawn: HashSet<*const T> = drawn.map(Arc::as_ptr).collect();
+        self.meshes.retain(|x, _| drawn.contains(x));
+    }
+}
+//which mesh a run of instances is drawn with. every hull, trimesh and heightfield gets its own
+enum Batch {
+    Cube,
+    Sphere,
+    Cylinder,
+    Plane,
+    Hull(Arc<ConvexHull>),
+    TriMesh(Arc<TriMesh>),
+    Heightfield(Arc<Heightfield>),
+}
 struct State {
     window: Arc<Window>,
     device: wgpu::Device,
@@ -45,9 +135,13 @@ struct State {
     surface: wgpu::Surface<'static>,
     surface_format: wgpu::TextureFormat,
     render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    cube_mesh: Mesh,
+    sphere_mesh: Mesh,
+    cylinder_mesh: Mesh,
+    plane_mesh: Mesh,
+    hull_meshes: MeshCache<ConvexHull>, //built the first time each hull is drawn
+    trimesh_meshes: MeshCache<TriMesh>, //likewise
+    heightfield_meshes: MeshCache<Heightfield>,
     camera: Camera,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
@@ -56,14 +150,17 @@ struct State {
     depth_texture: wgpu::Texture,
     world: World,
     instance_buffer: wgpu::Buffer,
-    floor_vertex_buffer: wgpu::Buffer,
-    floor_pipeline: wgpu::RenderPipeline,
+    instance_capacity: usize,
+    //instances are grouped by mesh, see raw_instances()
+    batches: Vec<(Batch, Range<u32>)>,
     tick_accumulator: f32,
     paused: bool,
+    step_stats: StepStatsWindow,
+    last_log: Instant,
 }
 
 impl State {
-    async fn new(window: Arc<Window>) -> State {
+    async fn new(window: Arc<Window>, scene: Scene, n: usize, settings: WorldSettings) -> State {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions::default())
@@ -80,23 +177,13 @@ impl State {
         let surface_format = cap.formats[0];
 
         let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(CUBE_VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(CUBE_INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-        let floor_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Floor Vertex Buffer"),
-            contents: bytemuck::cast_slice(FLOOR_VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        let cube_mesh = Mesh::new(&device, "Cube", CUBE_VERTICES, CUBE_INDICES);
+        let plane_mesh = Mesh::new(&device, "Plane", PLANE_VERTICES, PLANE_INDICES);
 
-        let num_indices = CUBE_INDICES.len() as u32;
+        let (sphere_vertices, sphere_indices) = sphere_mesh(12, 24);
+        let sphere_mesh = Mesh::new(&device, "Sphere", &sphere_vertices, &sphere_indices);
+        let (cylinder_vertices, cylinder_indices) = cylinder_mesh(24);
+        let cylinder_mesh = Mesh::new(&device, "Cylinder", &cylinder_vertices, &cylinder_indices);
 
         let camera = Camera {
             position: Vec3 {
@@ -149,16 +236,11 @@ impl State {
                 push_constant_ranges: &[],
             });
 
-        let world = World::new();
-        let mut raw_instances = [CuboidRaw::default(); N];
-        for (i, instance) in world.instances[..N].iter().enumerate() {
-            raw_instances[i] = Cuboid::to_raw(instance);
-        }
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Buffer"),
-            contents: bytemuck::cast_slice(&raw_instances),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
+        let world = World::new(scene, n, settings);
+        let (raw_instances, batches) = Self::raw_instances(&world);
+        let instance_capacity = raw_instances.len().max(1);
+        let instance_buffer = Self::create_instance_buffer(&device, instance_capacity);
+        queue.write_buffer(&instance_buffer, 0, bytemuck::cast_slice(&raw_instances));
 
         let depth_texture = Self::create_depth_texture(&device, size);
 
@@ -171,17 +253,7 @@ impl State {
             &[Vertex::desc(), CuboidRaw::desc()],
             true,
         );
-
-        let floor_pipeline = Self::make_pipeline(
-            &device,
-            &shader,
-            &render_pipeline_layout,
-            surface_format,
-            "vs_static",
-            &[Vertex::desc()],
-            false,
-        );
-        let state = State {
+        let mut state = State {
             window,
             device,
             queue,
@@ -189,9 +261,13 @@ impl State {
             surface,
             surface_format,
             render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            cube_mesh,
+            sphere_mesh,
+            cylinder_mesh,
+            plane_mesh,
+            hull_meshes: MeshCache::new(),
+            trimesh_meshes: MeshCache::new(),
+            heightfield_meshes: MeshCache::new(),
             camera,
             camera_buffer,
             camera_bind_group,
@@ -200,12 +276,15 @@ impl State {
             depth_texture,
             world,
             instance_buffer,
-            floor_vertex_buffer,
-            floor_pipeline,
+            instance_capacity,
+            batches,
             tick_accumulator: 0.0,
             paused: true,
+            step_stats: StepStatsWindow::new(180),
+            last_log: Instant::now(),
         };
         state.configure_surface();
+        state.build_meshes();
         state
     }
     fn create_depth_texture(
@@ -227,6 +306,178 @@ impl State {
             view_formats: &[],
         })
     }
+    //grouped by mesh so each one is a single draw call: cuboids, spheres, cylinders, then each
+    //hull in turn, then each trimesh
+    fn raw_instances(world: &World) -> (Vec<CuboidRaw>, Vec<(Batch, Range<u32>)>) {
+        let mut cuboids = Vec::new();
+        let mut spheres = Vec::new();
+        let mut cylinders = Vec::new();
+        let mut planes = Vec::new();
+        let mut hulls: HashMap<*const ConvexHull, (Arc<ConvexHull>, Vec<CuboidRaw>)> =
+            HashMap::new();
+        let mut trimeshes = Vec::new();
+        let mut heightfields = Vec::new();
+        let unscaled = Vec3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        };
+        //compounds are drawn as their children, each like a body of its own
+        let children = world.instances.iter().flat_map(|body| &body.children);
+        for body in world.instances.iter().chain(children) {
+            match &body.shape {
+                Shape::Cuboid => cuboids.push(body.to_raw()),
+                Shape::Sphere { .. } => spheres.push(body.to_raw()),
+                Shape::Capsule {
+                    radius,
+                    half_height,
+                } => {
+                    let (radius, half_height) = (*radius, *half_height);
+                    let diameter = radius * 2.0;
+                    let cap = Vec3 {
+                        x: diameter,
+                        y: diameter,
+                        z: diameter,
+                    };
+                    let up = body.face_axes[1] * half_height;
+                    spheres.push(body.to_raw_at(body.position + up, cap));
+                    spheres.push(body.to_raw_at(body.position - up, cap));
+                    cylinders.push(body.to_raw_at(
+                        body.position,
+                        Vec3 {
+                            x: diameter,
+                            y: half_height * 2.0,
+                            z: diameter,
+                        },
+                    ));
+                }
+                //the mesh is already the hull's size
+                Shape::ConvexHull(hull) => {
+                    let raw = body.to_raw_at(body.position, unscaled);
+                    hulls
+                        .entry(Arc::as_ptr(hull))
+                        .or_insert_with(|| (hull.clone(), Vec::new()))
+                        .1
+                        .push(raw);
+                }
+                //each is usually placed once, so there's no point grouping them
+                Shape::TriMesh(mesh) => {
+                    trimeshes.push((mesh.clone(), body.to_raw_at(body.position, unscaled)));
+                }
+                Shape::Heightfield(field) => {
+                    heightfields.push((field.clone(), body.to_raw_at(body.position, unscaled)));
+                }
+                //a big square turned to face the normal, through the plane's nearest point to the
+                //origin
+                Shape::Plane { .. } => {
+                    let (normal, offset) = body.plane().unwrap();
+                    let up = Vec3 {
+                        x: 0.0,
+                        y: 1.0,
+                        z: 0.0,
+                    };
+                    let rotation = match up.cross(&normal).normalize() {
+                        Some(axis) => Quaternion::from_angle(&axis, up.dot(&normal).acos()),
+                        None if normal.y > 0.0 => Quaternion::default(),
+                        None => Quaternion::from_angle(
+                            &Vec3 {
+                                x: 1.0,
+                                y: 0.0,
+                                z: 0.0,
+                            },
+                            PI,
+                        ),
+                    };
+                    let square = Cuboid {
+                        rotation,
+                        ..Default::default()
+                    };
+                    let size = Vec3 {
+                        x: PLANE_SIZE,
+                        y: 1.0,
+                        z: PLANE_SIZE,
+                    };
+                    planes.push(square.to_raw_at(normal * offset, size));
+                }
+                Shape::Compound(_) => (),
+            }
+        }
+        let mut raw_instances = Vec::new();
+        let mut batches = Vec::new();
+        let mut add = |batch, instances: Vec<CuboidRaw>| {
+            let start = raw_instances.len() as u32;
+            raw_instances.extend(instances);
+            batches.push((batch, start..raw_instances.len() as u32));
+        };
+        add(Batch::Cube, cuboids);
+        add(Batch::Sphere, spheres);
+        add(Batch::Cylinder, cylinders);
+        add(Batch::Plane, planes);
+        for (hull, instances) in hulls.into_values() {
+            add(Batch::Hull(hull), instances);
+        }
+        for (mesh, raw) in trimeshes {
+            add(Batch::TriMesh(mesh), vec![raw]);
+        }
+        for (field, raw) in heightfields {
+            add(Batch::Heightfield(field), vec![raw]);
+        }
+        (raw_instances, batches)
+    }
+    fn build_meshes(&mut self) {
+        self.hull_meshes
+            .retain(self.batches.iter().filter_map(|(batch, _)| match batch {
+                Batch::Hull(hull) => Some(hull),
+                _ => None,
+            }));
+        self.trimesh_meshes
+            .retain(self.batches.iter().filter_map(|(batch, _)| match batch {
+                Batch::TriMesh(trimesh) => Some(trimesh),
+                _ => None,
+            }));
+        self.heightfield_meshes
+            .retain(self.batches.iter().filter_map(|(batch, _)| match batch {
+                Batch::Heightfield(field) => Some(field),
+                _ => None,
+            }));
+        for (batch, _) in &self.batches {
+            match batch {
+                Batch::Hull(hull) => self.hull_meshes.get_or_build(hull, |hull| {
+                    let (vertices, indices) = hull_mesh(hull);
+                    Mesh::new(&self.device, "Hull", &vertices, &indices)
+                }),
+                Batch::TriMesh(trimesh) => self.trimesh_meshes.get_or_build(trimesh, |trimesh| {
+                    let (vertices, indices) = trimesh_mesh(trimesh);
+                    Mesh::with_indices(
+                        &self.device,
+                        "TriMesh",
+                        &vertices,
+                        &indices,
+                        wgpu::IndexFormat::Uint32,
+                    )
+                }),
+                Batch::Heightfield(field) => self.heightfield_meshes.get_or_build(field, |field| {
+                    let (vertices, indices) = heightfield_mesh(field);
+                    Mesh::with_indices(
+                        &self.device,
+                        "Heightfield",
+                        &vertices,
+                        &indices,
+                        wgpu::IndexFormat::Uint32,
+                    )
+                }),
+                _ => (),
+            }
+        }
+    }
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * std::mem::size_of::<CuboidRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
     fn make_pipeline(
         device: &wgpu::Device,
         shader: &wgpu::ShaderModule,
@@ -319,17 +570,27 @@ impl State {
         if !self.paused {
             self.tick_accumulator += dt.as_secs_f32();
             let mut tick_count = 0;
-            while self.tick_accumulator >= PHYSICS_DT && tick_count < 3 {
-                self.world.update();
-                self.tick_accumulator -= PHYSICS_DT;
+            let physics_dt = self.world.settings.dt;
+            while self.tick_accumulator >= physics_dt && tick_count < 3 {
+                let stats = self.world.update();
+                self.step_stats.push(stats);
+                self.tick_accumulator -= physics_dt;
                 tick_count += 1;
             }
+            if self.last_log.elapsed() > Duration::from_secs(1) {
+                self.log_stats();
+                self.last_log = Instant::now();
+            }
         }
-        let mut raw_instances = [CuboidRaw::default(); N];
-        for (i, instance) in self.world.instances[..N].iter().enumerate() {
-            raw_instances[i] = Cuboid::to_raw(instance);
+        let (raw_instances, batches) = Self::raw_instances(&self.world);
+        if raw_instances.len() > self.instance_capacity {
+            //grow geometrically so spawning bodies one by one doesn't realloc every frame
+            self.instance_capacity = raw_instances.len().next_power_of_two();
+            self.instance_buffer =
+                Self::create_instance_buffer(&self.device, self.instance_capacity);
         }
-
+        self.batches = batches;
+        self.build_meshes();
         self.queue.write_buffer(
             &self.instance_buffer,
             0,
@@ -337,6 +598,29 @@ impl State {
         );
     }
 
+    fn log_stats(&self) {
+        let ms = |f: fn(&StepStats) -> Duration| {
+            self.step_stats
+                .summary(|x| f(x).as_secs_f64() * 1000.0)
+                .map_or(0.0, |x| x.mean)
+        };
+        let pair_checks = self
+            .step_stats
+            .summary(|x| x.pair_checks as f64)
+            .map_or(0.0, |x| x.mean);
+        print!(
+            "\rtick: {:.2} ms, grid init: {:.2} ms, static: {:.2} ms, check count: {:.0}, broad: {:.2} ms, narrow {:.2} ms, impulse: {:.2} ms",
+            ms(|x| x.total_time),
+            ms(|x| x.grid_init_time),
+            ms(|x| x.static_time),
+            pair_checks,
+            ms(|x| x.broad_time),
+            ms(|x| x.narrow_time),
+            ms(|x| x.impulse_time),
+        );
+        io::stdout().flush().unwrap();
+    }
+
     fn render(&mut self) {
         let surface_texture = self
             .surface
@@ -378,14 +662,21 @@ impl State {
 
         renderpass.set_pipeline(&self.render_pipeline);
         renderpass.set_bind_group(0, &self.camera_bind_group, &[]);
-        renderpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         renderpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        renderpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        renderpass.draw_indexed(0..self.num_indices, 0, 0..N as u32);
-
-        renderpass.set_pipeline(&self.floor_pipeline);
-        renderpass.set_vertex_buffer(0, self.floor_vertex_buffer.slice(..));
-        renderpass.draw(0..6, 0..1);
+        for (batch, instances) in &self.batches {
+            let mesh = match batch {
+                Batch::Cube => &self.cube_mesh,
+                Batch::Sphere => &self.sphere_mesh,
+                Batch::Cylinder => &self.cylinder_mesh,
+                Batch::Plane => &self.plane_mesh,
+                Batch::Hull(hull) => self.hull_meshes.get(hull),
+                Batch::TriMesh(trimesh) => self.trimesh_meshes.get(trimesh),
+                Batch::Heightfield(field) => self.heightfield_meshes.get(field),
+            };
+            renderpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            renderpass.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+            renderpass.draw_indexed(0..mesh.num_indices, 0, instances.clone());
+        }
 
         drop(renderpass);
 
@@ -395,10 +686,12 @@ impl State {
     }
 }
 
-#[derive(Default)]
 struct App {
     state: Option<State>,
     last_time: Option<Instant>,
+    scene: Scene,
+    n: usize,
+    settings: WorldSettings,
 }
 
 impl ApplicationHandler for App {
@@ -408,7 +701,12 @@ impl ApplicationHandler for App {
                 .create_window(Window::default_attributes())
                 .unwrap(),
         );
-        let state = pollster::block_on(State::new(window.clone()));
+        let state = pollster::block_on(State::new(
+            window.clone(),
+            self.scene,
+            self.n,
+            self.settings,
+        ));
         self.state = Some(state);
         window.request_redraw();
         if window.set_cursor_grab(CursorGrabMode::Locked).is_err() {
@@ -469,9 +767,15 @@ impl ApplicationHandler for App {
         }
     }
 }
-pub fn init() {
+pub fn init(scene: Scene, n: usize, settings: WorldSettings) {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
-    let mut app = App::default();
+    let mut app = App {
+        state: None,
+        last_time: None,
+        scene,
+        n,
+        settings,
+    };
     event_loop.run_app(&mut app).unwrap();
 }
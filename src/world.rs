@@ -1,34 +1,101 @@
 use std::{
-    collections::HashSet,
-    io::{self, Write},
-    time::{Duration, Instant},
+    collections::{HashMap, HashSet},
+    time::Instant,
 };
 
 use crate::{
+    arena::{Arena, Handle},
+    compound::Child,
     hash_grid::HashGrid,
+    island::{Island, build_islands, solve_islands},
+    joint::{Joint, JointHandle, JointKind, WORLD_SLOT},
+    material::Material,
     math::{EPSILON, Mat3, Quaternion, Vec3},
-    physics::{CollisionInfo, detect_collision, resolve_collisions},
-    scenes::{N, Scene},
+    physics::{CollisionInfo, ContactPoint, MAX_CONTACT_POINTS, detect_collisions, time_of_impact},
+    scenes::Scene,
+    shape::Shape,
+    stats::StepStats,
 };
 
+pub type BodyHandle = Handle;
+type ContactKey = (BodyHandle, BodyHandle, (usize, usize));
+
 pub struct World {
-    pub instances: Vec<Cuboid>,
-    floor: Cuboid,
+    pub instances: Arena<Cuboid>,
+    pub settings: WorldSettings,
+    //planes, meshes and heightfields, tested against every awake body rather than going in the
+    //hash grid
+    static_bodies: Vec<BodyHandle>,
+    joints: Arena<Joint>,
     collisions: Vec<CollisionInfo>,
     hash_grid: HashGrid,
-    last_log: Instant,
-    last_tick: Instant,
+    stats: StepStats,
     vis_collisions: HashSet<(usize, usize)>,
+    sleeping_islands: HashMap<usize, Vec<BodyHandle>>,
+    next_island_id: usize,
+    to_wake: Vec<usize>,
+    islands: Vec<Island>,
+    //last tick's manifolds by (instance, other, parts), for warm starting
+    contact_cache: HashMap<ContactKey, [Option<ContactPoint>; MAX_CONTACT_POINTS]>,
+    //last tick's separating axis for pairs whose aabbs overlapped without colliding
+    separating_axes: HashMap<(BodyHandle, BodyHandle), usize>,
 }
 
-pub const PHYSICS_DT: f32 = 1.0 / 180.0; //180 for accuracy, 60 for speed
 // SI units
-const GRAV_ACCEL: Vec3 = Vec3 {
-    x: 0.0,
-    y: -9.81,
-    z: 0.0,
-};
-const GLOBAL_AXES: [Vec3; 3] = [
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldSettings {
+    pub gravity: Vec3,
+    pub dt: f32, //1/180 for accuracy, 1/60 for speed
+    pub solver_iterations: usize,
+    pub max_solver_iterations: usize, //islands get one iteration per body, clamped to this
+    pub solver_threads: usize,        //islands are spread over this many workers
+    pub baumgarte_bias: f32,          //fraction of penetration corrected per tick
+    pub penetration_tolerance: f32,   //depth left uncorrected so resting contacts don't jitter
+    pub restitution_threshold: f32,   //ms^-1, slower impacts don't bounce
+    pub warm_starting: bool,          //seed the solver with last tick's impulses
+    pub sat_coherence: bool,          //try last tick's separating axis first
+    pub speculative_contacts: bool,   //contacts for pairs that are apart but closing within a tick
+    pub ccd_motion_threshold: f32, //fraction of its smallest extent a ccd body moves per tick before it's swept
+    pub allow_sleeping: bool,
+    pub sleep_linear_threshold: f32,  //ms^-1
+    pub sleep_angular_threshold: f32, //rads^-1
+    pub time_to_sleep: f32,           //s, a whole island must stay under the thresholds this long
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            gravity: Vec3 {
+                x: 0.0,
+                y: -9.81,
+                z: 0.0,
+            },
+            dt: 1.0 / 180.0,
+            solver_iterations: 8,
+            max_solver_iterations: 32,
+            solver_threads: 1,
+            baumgarte_bias: 0.3,
+            penetration_tolerance: 0.001,
+            //gravity alone adds 5cms^-1 per tick at 180hz, which shouldn't bounce
+            restitution_threshold: 0.5,
+            warm_starting: true,
+            sat_coherence: true,
+            speculative_contacts: true,
+            ccd_motion_threshold: 0.5,
+            allow_sleeping: true,
+            //resting contacts still jitter by a few cms^-1
+            sleep_linear_threshold: 0.25,
+            sleep_angular_threshold: 0.25,
+            time_to_sleep: 0.5,
+        }
+    }
+}
+//room in the collision list per body, about 8 neighbours with each pair counted once, plus the
+//floor
+const COLLISIONS_PER_BODY: usize = 8 / 2 + 1;
+//how far past a removed body's bounds a sleeping body can be and still count as resting on it
+const SUPPORT_MARGIN: f32 = 0.05;
+pub(crate) const GLOBAL_AXES: [Vec3; 3] = [
     Vec3 {
         x: 1.0,
         y: 0.0,
@@ -47,48 +114,216 @@ const GLOBAL_AXES: [Vec3; 3] = [
 ];
 
 impl World {
-    #[allow(clippy::new_without_default)]
-    pub fn new() -> Self {
-        let mut instances = Vec::with_capacity(N + 1);
-        Scene::Cube.populate_scene(&mut instances);
+    pub fn new(scene: Scene, n: usize, settings: WorldSettings) -> Self {
+        let mut scene_instances = Vec::with_capacity(n);
+        let mut scene_joints = Vec::new();
+        scene.populate_scene(&mut scene_instances, &mut scene_joints, n);
+        let n = scene_instances.len(); //some scenes round n down
 
-        let mut floor = Cuboid {
-            scale: Vec3 {
-                x: 1000.0,
-                y: 1.0,
-                z: 1000.0,
-            },
-            position: Vec3 {
-                x: 0.0,
-                y: -0.5,
-                z: 0.0,
+        let mut world = Self {
+            instances: Arena::with_capacity(n),
+            settings,
+            static_bodies: Vec::new(),
+            joints: Arena::with_capacity(scene_joints.len()),
+            collisions: Vec::with_capacity(n * COLLISIONS_PER_BODY),
+            hash_grid: HashGrid::new(&scene_instances),
+            stats: StepStats::default(),
+            vis_collisions: HashSet::with_capacity(n * COLLISIONS_PER_BODY),
+            sleeping_islands: HashMap::new(),
+            next_island_id: 0,
+            to_wake: Vec::new(),
+            islands: Vec::new(),
+            contact_cache: HashMap::new(),
+            separating_axes: HashMap::new(),
+        };
+        let handles: Vec<BodyHandle> = scene_instances
+            .into_iter()
+            .map(|x| world.add_body(x))
+            .collect();
+        for joint in scene_joints {
+            world.add_joint(
+                joint.kind,
+                handles[joint.a],
+                joint.b.map(|x| handles[x]),
+                joint.anchor_a,
+                joint.anchor_b,
+            );
+        }
+        world
+    }
+
+    //desc.index is ignored, derived fields are recalculated
+    pub fn add_body(&mut self, desc: Cuboid) -> BodyHandle {
+        self.hash_grid.fit(&desc);
+        let is_static = desc.shape.is_static();
+        let handle = self.instances.insert_with(|handle| {
+            let mut instance = desc;
+            instance.index = handle.index();
+            instance.frozen |= is_static;
+            instance.asleep = false;
+            instance.sleep_timer = 0.0;
+            instance.update_derived();
+            instance
+        });
+        if is_static {
+            self.static_bodies.push(handle);
+        }
+        self.hash_grid.reserve(self.instances.len());
+        //keep the same per-body headroom new() starts with
+        self.collisions.reserve(COLLISIONS_PER_BODY);
+        self.vis_collisions.reserve(COLLISIONS_PER_BODY);
+        handle
+    }
+
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<Cuboid> {
+        //whatever it was holding up needs to fall. frozen and static bodies never sleep, so wake
+        //every sleeping island resting against it rather than just its own
+        let reach = self.instances.get(handle)?.aabb.expand(SUPPORT_MARGIN);
+        let supported: Vec<BodyHandle> = (0..self.instances.slot_count())
+            .filter_map(|i| self.instances.handle_at(i))
+            .filter(|&x| {
+                let body = self.instances.get(x).unwrap();
+                body.asleep && body.aabb.intersects(&reach)
+            })
+            .collect();
+        self.wake_body(handle);
+        for body in supported {
+            self.wake_body(body);
+        }
+        self.static_bodies.retain(|&x| x != handle);
+        let attached: Vec<JointHandle> = (0..self.joints.slot_count())
+            .filter_map(|i| self.joints.handle_at(i))
+            .filter(|&x| {
+                let joint = self.joints.get(x).unwrap();
+                joint.a == handle || joint.b == Some(handle)
+            })
+            .collect();
+        for joint in attached {
+            self.remove_joint(joint);
+        }
+        self.instances.remove(handle)
+    }
+
+    //anchors are in world space, the point on each body that's held to the other, and are
+    //usually the same point. b is None to join a to the world, anchor_b then staying put. None if
+    //either body doesn't exist, they're the same body or kind's axis is zero
+    pub fn add_joint(
+        &mut self,
+        kind: JointKind,
+        a: BodyHandle,
+        b: Option<BodyHandle>,
+        anchor_a: Vec3,
+        anchor_b: Vec3,
+    ) -> Option<JointHandle> {
+        if b == Some(a) {
+            return None;
+        }
+        let joint = Joint::new(
+            kind,
+            (a, self.instances.get(a)?),
+            match b {
+                Some(b) => Some((b, self.instances.get(b)?)),
+                None => None,
             },
-            index: N,
-            frozen: true,
-            ..Default::default()
+            anchor_a,
+            anchor_b,
+        )?;
+        self.wake_body(a);
+        if let Some(b) = b {
+            self.wake_body(b);
+        }
+        Some(self.joints.insert_with(|_| joint))
+    }
+
+    pub fn remove_joint(&mut self, handle: JointHandle) -> Option<Joint> {
+        let joint = self.joints.remove(handle)?;
+        self.wake_body(joint.a);
+        if let Some(b) = joint.b {
+            self.wake_body(b);
+        }
+        Some(joint)
+    }
+
+    pub fn joint(&self, handle: JointHandle) -> Option<&Joint> {
+        self.joints.get(handle)
+    }
+
+    pub fn joints(&self) -> impl Iterator<Item = &Joint> {
+        self.joints.iter()
+    }
+
+    //wakes the whole island the body fell asleep with
+    pub fn wake_body(&mut self, handle: BodyHandle) {
+        self.wake_island(handle, true);
+    }
+
+    //contact wakeups keep the timers, so an island that only split for a tick can go straight
+    //back to sleep instead of flickering
+    fn wake_island(&mut self, handle: BodyHandle, reset_timers: bool) {
+        let Some(body) = self.instances.get(handle) else {
+            return;
+        };
+        if !body.asleep {
+            return;
+        }
+        let Some(members) = self.sleeping_islands.remove(&body.sleep_island) else {
+            return;
+        };
+        for member in members {
+            if let Some(member) = self.instances.get_mut(member) {
+                member.asleep = false;
+                if reset_timers {
+                    member.sleep_timer = 0.0;
+                }
+            }
+        }
+    }
+
+    //point is in world space
+    pub fn apply_impulse(&mut self, handle: BodyHandle, impulse: Vec3, point: Vec3) {
+        self.wake_body(handle);
+        let Some(body) = self.instances.get_mut(handle) else {
+            return;
         };
-        floor.update_derived();
-        instances.push(floor);
+        body.velocity += impulse * body.inverse_mass;
+        body.angular_velocity +=
+            &body.inverse_inertia * &(point - body.centre_of_mass()).cross(&impulse);
+    }
 
-        let hash_grid = HashGrid::new(&instances, N);
-        Self {
-            instances,
-            floor,
-            collisions: Vec::with_capacity(N * 8 / 2 + N),
-            hash_grid,
-            last_log: Instant::now(),
-            last_tick: Instant::now(),
-            vis_collisions: HashSet::with_capacity(N * 8 / 2 + N),
-        }
-    }
-
-    pub fn update(&mut self) {
-        let dt = PHYSICS_DT;
-        for i in 0..N {
-            let instance = &mut self.instances[i];
-            if !instance.frozen {
-                instance.velocity += GRAV_ACCEL * dt;
-                instance.position += instance.velocity * dt;
+    pub fn body(&self, handle: BodyHandle) -> Option<&Cuboid> {
+        self.instances.get(handle)
+    }
+
+    //call update_derived() after moving a frozen body, it won't be refreshed otherwise.
+    //call wake_body() after changing a sleeping body's velocity
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut Cuboid> {
+        self.instances.get_mut(handle)
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = &Cuboid> {
+        self.instances.iter()
+    }
+
+    //from the last tick, useful for seeing which bodies influence each other
+    pub fn islands(&self) -> &[Island] {
+        &self.islands
+    }
+
+    pub fn stats(&self) -> &StepStats {
+        &self.stats
+    }
+
+    pub fn update(&mut self) -> StepStats {
+        let dt = self.settings.dt;
+        let mut stats = StepStats::default();
+        let start_time = Instant::now();
+        let mut ccd_bodies = Vec::new();
+        for instance in self.instances.iter_mut() {
+            if instance.is_awake() {
+                let start = instance.position;
+                instance.velocity += self.settings.gravity * dt;
+                //it turns about the centre of mass, which drags the geometric centre round it
+                let centre = instance.centre_of_mass() + instance.velocity * dt;
                 if instance.angular_velocity.mag() > EPSILON * dt {
                     instance.rotation = (Quaternion::from_angle(
                         &instance.angular_velocity.normalize().unwrap(),
@@ -96,41 +331,127 @@ impl World {
                     ) * instance.rotation)
                         .normalize();
                 }
+                instance.position = centre - instance.mass_offset.rotate(instance.rotation);
 
                 instance.update_derived();
+                stats.bodies_awake += 1;
+                if instance.ccd
+                    && (instance.position - start).mag()
+                        > self.settings.ccd_motion_threshold * instance.smallest_extent()
+                {
+                    ccd_bodies.push((instance.index, start));
+                }
             }
         }
         let grid_init_time = Instant::now();
+        stats.integrate_time = grid_init_time - start_time;
         self.hash_grid.clear();
-        self.hash_grid.init(&self.instances);
-        let floor_time = Instant::now();
-
-        let mut check_count = 0;
-        for i in 0..N {
-            let instance = &self.instances[i];
-            if instance.aabb.intersects(&self.floor.aabb) {
-                if let Some(collision_info) = detect_collision(instance, &self.floor) {
-                    if self.collisions.len() < self.collisions.capacity() {
-                        self.collisions.push(collision_info);
-                    } else {
-                        eprintln!("self.collisions capacity exceeded");
-                    }
+        //pairs closer than they can move in a tick get speculative contacts
+        let speculative_contacts = self.settings.speculative_contacts;
+        let margin = |x: &Cuboid| {
+            if speculative_contacts {
+                x.speculative_margin(dt)
+            } else {
+                0.0
+            }
+        };
+        self.hash_grid.init(
+            self.instances
+                .iter()
+                .filter(|x| !x.shape.is_static())
+                .map(|x| (x.index, x.aabb.expand(margin(x)))),
+        );
+        let ccd_time = Instant::now();
+        stats.grid_init_time = ccd_time - grid_init_time;
+        //pairs and sweeps share a fixed amount of room each tick. static contacts don't count
+        //towards it, so how much is left never depends on earlier ticks
+        let mut room = self.instances.len() * COLLISIONS_PER_BODY;
+        for (index, start) in ccd_bodies {
+            self.sweep(index, start, &mut room);
+        }
+        let static_time = Instant::now();
+        stats.ccd_time = static_time - ccd_time;
+
+        //joined bodies never collide with each other
+        let joint_bodies: Vec<(usize, usize)> = self
+            .joints
+            .iter()
+            .map(|x| (x.a.index(), x.b.map_or(WORLD_SLOT, |b| b.index())))
+            .collect();
+        for &(a, b) in &joint_bodies {
+            if b == WORLD_SLOT {
+                continue;
+            }
+            self.vis_collisions.insert((a.min(b), a.max(b)));
+            //like a contact, a sleeping body held by an awake one acts static for this tick
+            let awake = |index: usize| self.instances.get_by_index(index).unwrap().is_awake();
+            let asleep = |index: usize| self.instances.get_by_index(index).unwrap().asleep;
+            if awake(a) && asleep(b) {
+                self.to_wake.push(b);
+            } else if awake(b) && asleep(a) {
+                self.to_wake.push(a);
+            }
+        }
+
+        let previous_axes = std::mem::take(&mut self.separating_axes);
+        let sat_coherence = self.settings.sat_coherence;
+        let instances = &self.instances;
+        let pair_key = |instance: &Cuboid, other: &Cuboid| {
+            (
+                instances.handle_at(instance.index).unwrap(),
+                instances.handle_at(other.index).unwrap(),
+            )
+        };
+        for instance in self.instances.iter() {
+            let distance = margin(instance);
+            if !instance.is_awake() {
+                continue;
+            }
+            for &handle in &self.static_bodies {
+                let other = self.instances.get(handle).unwrap();
+                let pair = (
+                    instance.index.min(other.index),
+                    instance.index.max(other.index),
+                );
+                if instance.index == other.index
+                    || !instance.aabb.expand(distance).intersects(&other.aabb)
+                    || self.vis_collisions.contains(&pair)
+                {
+                    continue;
+                }
+                let key = pair_key(instance, other);
+                let mut separating_axis =
+                    previous_axes.get(&key).copied().filter(|_| sat_coherence);
+                detect_collisions(
+                    instance,
+                    other,
+                    &mut separating_axis,
+                    distance,
+                    &mut self.collisions,
+                );
+                if sat_coherence && let Some(axis) = separating_axis {
+                    self.separating_axes.insert(key, axis);
                 }
             }
         }
+        //a mesh can touch a body at any number of triangles, which shouldn't use up the room
+        //left for pairs
+        self.collisions.reserve(room);
 
         let broad_time = Instant::now();
-        let mut narrow_time = 0;
+        stats.static_time = broad_time - static_time;
+        //a pair of compounds can touch at several children, each a collision of its own
+        let mut pair_collisions = Vec::new();
         for bucket in &self.hash_grid.buckets {
             if bucket.len() <= 1 {
                 continue;
             }
             for i in 0..bucket.len() {
-                let instance = &self.instances[bucket[i]];
-                for j in i + 1..bucket.len() {
-                    let other = &self.instances[bucket[j]];
-                    check_count += 1;
-                    if instance.index == other.index {
+                let instance = self.instances.get_by_index(bucket[i]).unwrap();
+                for &other_index in &bucket[i + 1..] {
+                    let other = self.instances.get_by_index(other_index).unwrap();
+                    stats.pair_checks += 1;
+                    if instance.index == other.index || !(instance.is_awake() || other.is_awake()) {
                         continue;
                     }
                     let pair = (
@@ -141,46 +462,228 @@ impl World {
                         continue;
                     }
                     let pre = Instant::now();
-                    if instance.aabb.intersects(&other.aabb) {
-                        if let Some(collision_info) = detect_collision(instance, other) {
-                            if self.collisions.len() < self.collisions.capacity() {
-                                self.collisions.push(collision_info);
-                                self.vis_collisions.insert(pair);
-                            } else {
-                                eprintln!("self.collisions capacity exceeded");
+                    let distance = margin(instance) + margin(other);
+                    if instance.aabb.expand(distance).intersects(&other.aabb) {
+                        let key = pair_key(instance, other);
+                        let mut separating_axis =
+                            previous_axes.get(&key).copied().filter(|_| sat_coherence);
+                        detect_collisions(
+                            instance,
+                            other,
+                            &mut separating_axis,
+                            distance,
+                            &mut pair_collisions,
+                        );
+                        if sat_coherence && let Some(axis) = separating_axis {
+                            self.separating_axes.insert(key, axis);
+                        }
+                    }
+                    if !pair_collisions.is_empty() {
+                        if pair_collisions.len() <= room {
+                            room -= pair_collisions.len();
+                            self.collisions.append(&mut pair_collisions);
+                            self.vis_collisions.insert(pair);
+                            //sleeping bodies act static for the rest of this tick
+                            if instance.asleep {
+                                self.to_wake.push(instance.index);
+                            } else if other.asleep {
+                                self.to_wake.push(other.index);
                             }
+                        } else {
+                            pair_collisions.clear();
+                            eprintln!("self.collisions capacity exceeded");
                         }
                     }
-                    narrow_time += pre.elapsed().as_nanos();
+                    stats.narrow_time += pre.elapsed();
                 }
             }
         }
-        let broad_phase_time = (Instant::now() - broad_time).as_nanos() - narrow_time;
         let impulse_time = Instant::now();
-        resolve_collisions(&self.collisions, &mut self.instances, dt);
-        if self.last_log.elapsed() > Duration::from_secs(1) {
-            print!(
-                "\rTPS: {}, grid init: {} ms, floor: {} ms, check count: {}, broad: {} ms, narrow {} ms, impulse: {} ms",
-                self.last_tick.elapsed().as_millis(),
-                (floor_time - grid_init_time).as_millis(),
-                (broad_time - floor_time).as_millis(),
-                check_count,
-                broad_phase_time / 1000000,
-                narrow_time / 1000000,
-                impulse_time.elapsed().as_millis()
-            );
-            io::stdout().flush().unwrap();
-            self.last_log = Instant::now();
+        stats.broad_time = (impulse_time - broad_time).saturating_sub(stats.narrow_time);
+        stats.collisions = self.collisions.len();
+        stats.contacts = self
+            .collisions
+            .iter()
+            .map(|x| x.manifold.iter().flatten().count())
+            .sum();
+        if self.settings.warm_starting {
+            for info in &mut self.collisions {
+                let key = (
+                    self.instances.handle_at(info.instance_index).unwrap(),
+                    self.instances.handle_at(info.other_index).unwrap(),
+                    info.parts,
+                );
+                if let Some(previous) = self.contact_cache.get(&key) {
+                    info.warm_start(previous);
+                }
+            }
+        }
+        self.islands = build_islands(
+            &self.collisions,
+            &joint_bodies,
+            &self.instances,
+            &self.settings,
+        );
+        stats.islands = self.islands.iter().filter(|x| x.is_connected()).count();
+        stats.solver_iterations = solve_islands(
+            &self.islands,
+            &mut self.collisions,
+            &mut self.joints,
+            &mut self.instances,
+            &self.settings,
+        );
+        stats.impulse_time = impulse_time.elapsed();
+        if self.settings.allow_sleeping {
+            self.update_sleep();
+        } else {
+            self.wake_all();
+        }
+        //touched by an awake body this tick
+        for index in std::mem::take(&mut self.to_wake) {
+            if let Some(handle) = self.instances.handle_at(index) {
+                self.wake_island(handle, false);
+            }
+        }
+        //pairs that stopped touching (or fell asleep) drop out and start cold next time
+        self.contact_cache.clear();
+        if self.settings.warm_starting {
+            for info in &self.collisions {
+                let key = (
+                    self.instances.handle_at(info.instance_index).unwrap(),
+                    self.instances.handle_at(info.other_index).unwrap(),
+                    info.parts,
+                );
+                self.contact_cache.insert(key, info.manifold);
+            }
         }
         self.collisions.clear();
         self.vis_collisions.clear();
-        self.last_tick = Instant::now();
+        stats.total_time = start_time.elapsed();
+        self.stats = stats;
+        stats
+    }
+
+    //pulls a ccd body back to just before the first thing it hit on the way from start, and
+    //leaves a speculative contact so the solver stops it closing the rest of the gap
+    fn sweep(&mut self, index: usize, start: Vec3, room: &mut usize) {
+        let body = self.instances.get_by_index(index).unwrap().clone();
+        let displacement = body.position - start;
+        let swept = body.aabb.union(&body.aabb.translate(-displacement));
+        //not in the grid
+        let mut candidates: Vec<usize> = self.static_bodies.iter().map(|x| x.index()).collect();
+        self.hash_grid.query(&swept, |other_index| {
+            if other_index != index && !candidates.contains(&other_index) {
+                candidates.push(other_index);
+            }
+        });
+        let mut first_hit: Option<(f32, f32, usize)> = None;
+        for other_index in candidates {
+            let other = self.instances.get_by_index(other_index).unwrap();
+            if swept.intersects(&other.aabb)
+                && let Some((apart, touching)) = time_of_impact(&body, start, other)
+                && first_hit.is_none_or(|x| apart < x.0)
+            {
+                first_hit = Some((apart, touching, other_index));
+            }
+        }
+        let Some((apart, touching, other_index)) = first_hit else {
+            return;
+        };
+
+        let other = self.instances.get_by_index(other_index).unwrap();
+        //a bit past the first touch so sat gets a usable normal, the depth is corrected back below
+        let probe = touching.max(apart + self.settings.penetration_tolerance / displacement.mag());
+        let mut hit = body.clone();
+        hit.position = start + displacement * probe.min(1.0);
+        hit.update_derived();
+        let mut collisions = Vec::new();
+        detect_collisions(&hit, other, &mut None, 0.0, &mut collisions);
+        if collisions.is_empty() {
+            return;
+        }
+        //move the contacts back to where the body stops
+        let shift = hit.position - (start + displacement * apart);
+        for collision_info in &mut collisions {
+            let normal = collision_info.normal;
+            for point in collision_info.manifold.iter_mut().flatten() {
+                point.point -= shift;
+                point.depth += shift.dot(&normal);
+            }
+        }
+        let other_asleep = other.asleep;
+
+        let body = self.instances.get_mut_by_index(index).unwrap();
+        body.position = start + displacement * apart;
+        body.update_derived();
+        //its cells from init() are where it would have ended up
+        let aabb = if self.settings.speculative_contacts {
+            body.aabb.expand(body.speculative_margin(self.settings.dt))
+        } else {
+            body.aabb
+        };
+        self.hash_grid.insert(index, &aabb);
+        if collisions.len() <= *room {
+            *room -= collisions.len();
+            self.collisions.extend(collisions);
+            self.vis_collisions
+                .insert((index.min(other_index), index.max(other_index)));
+            if other_asleep {
+                self.to_wake.push(other_index);
+            }
+        } else {
+            eprintln!("self.collisions capacity exceeded");
+        }
+    }
+
+    fn update_sleep(&mut self) {
+        let settings = self.settings;
+        for island in &self.islands {
+            let mut island_timer = f32::INFINITY;
+            for &index in &island.bodies[..island.awake_count] {
+                let instance = self.instances.get_mut_by_index(index).unwrap();
+                if instance.velocity.mag() < settings.sleep_linear_threshold
+                    && instance.angular_velocity.mag() < settings.sleep_angular_threshold
+                {
+                    instance.sleep_timer += settings.dt;
+                } else {
+                    instance.sleep_timer = 0.0;
+                }
+                island_timer = island_timer.min(instance.sleep_timer);
+            }
+            if island_timer < settings.time_to_sleep {
+                continue;
+            }
+            self.next_island_id += 1;
+            let mut members = Vec::with_capacity(island.awake_count);
+            for &index in &island.bodies[..island.awake_count] {
+                members.push(self.instances.handle_at(index).unwrap());
+                let instance = self.instances.get_mut_by_index(index).unwrap();
+                instance.asleep = true;
+                instance.sleep_island = self.next_island_id;
+                instance.velocity = Vec3::default();
+                instance.angular_velocity = Vec3::default();
+            }
+            self.sleeping_islands.insert(self.next_island_id, members);
+        }
+    }
+
+    //for when sleeping's been turned off with bodies still asleep
+    fn wake_all(&mut self) {
+        for (_, members) in self.sleeping_islands.drain() {
+            for member in members {
+                if let Some(member) = self.instances.get_mut(member) {
+                    member.asleep = false;
+                    member.sleep_timer = 0.0;
+                }
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{compound::Compound, heightfield::Heightfield, hull::ConvexHull, trimesh::TriMesh};
 
     #[test]
     fn test_cuboid_model_matrix() {
@@ -215,27 +718,902 @@ mod tests {
             assert!((expected - actual).abs() < EPSILON);
         }
     }
+
+    #[test]
+    fn test_world_sized_from_scene() {
+        //cube scene rounds down to the nearest perfect cube
+        let mut world = World::new(Scene::Cube, 30, WorldSettings::default());
+        assert_eq!(world.bodies().count(), 28);
+        assert_eq!(world.instances.len(), 28);
+        //the floor's an ordinary body the scene added
+        assert!(world.body(world.static_bodies[0]).unwrap().frozen);
+        world.update();
+
+        //the ramp's tiles are part of the count
+        for scene in [Scene::Balls, Scene::Rocks] {
+            let world = World::new(scene, 400, WorldSettings::default());
+            let count = world.bodies().count() - 1;
+            assert!(count <= 400 && count > 380);
+            assert!(world.bodies().any(|x| x.frozen && x.shape == Shape::Cuboid));
+        }
+
+        for scene in [Scene::Meteor, Scene::InvertedMeteor] {
+            let mut world = World::new(scene, 0, WorldSettings::default());
+            assert_eq!(world.bodies().count(), 1);
+            world.update();
+        }
+    }
+
+    #[test]
+    fn test_catapult_throws() {
+        //the heavy block lands on the short arm of a lever hinged to the fulcrum, flinging the
+        //projectile off the long arm
+        let mut world = World::new(Scene::Catapult, 4, WorldSettings::default());
+        assert_eq!(world.joints().count(), 1);
+        let projectile = world.bodies().find(|x| x.ccd).unwrap().index;
+        let projectile = world.instances.handle_at(projectile).unwrap();
+        let mut highest = 0.0f32;
+        for _ in 0..360 {
+            world.update();
+            highest = highest.max(world.body(projectile).unwrap().position.y);
+        }
+        assert!(highest > 5.0);
+        let joint = world.joints().next().unwrap();
+        let (fulcrum, lever) = (
+            world.body(joint.a).unwrap(),
+            world.body(joint.b.unwrap()).unwrap(),
+        );
+        let pivot = |x: &Cuboid, anchor: Vec3| x.position + anchor.rotate(x.rotation);
+        assert!((pivot(fulcrum, joint.anchor_a) - pivot(lever, joint.anchor_b)).mag() < 0.01);
+    }
+
+    #[test]
+    fn test_mass_from_volume() {
+        let small = Cuboid::default();
+        let big = Cuboid {
+            scale: Vec3 {
+                x: 2.0,
+                y: 2.0,
+                z: 2.0,
+            },
+            ..Default::default()
+        };
+        assert!((big.get_mass() / small.get_mass() - 8.0).abs() < EPSILON);
+        //uniform scaling by 2 scales inertia by 2^5
+        let ratio = small.get_inverse_moment_of_inertia().array[0]
+            / big.get_inverse_moment_of_inertia().array[0];
+        assert!((ratio - 32.0).abs() < EPSILON);
+
+        let overridden = Cuboid {
+            mass_override: Some(4.0),
+            inertia_override: Some(Vec3 {
+                x: 1.0,
+                y: 2.0,
+                z: 4.0,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(overridden.get_inverse_mass(), 0.25);
+        assert_eq!(
+            overridden.get_inverse_moment_of_inertia().array,
+            [1.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.25]
+        );
+    }
+
+    #[test]
+    fn test_cached_mass_properties() {
+        let mut cuboid = Cuboid {
+            scale: Vec3 {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 1.0,
+                    y: 1.0,
+                    z: 0.0,
+                },
+                0.7,
+            ),
+            ..Default::default()
+        };
+        cuboid.update_derived();
+        assert_eq!(cuboid.inverse_mass, cuboid.get_inverse_mass());
+        assert_eq!(
+            cuboid.inverse_inertia,
+            cuboid.get_inverse_moment_of_inertia()
+        );
+
+        cuboid.frozen = true;
+        cuboid.update_derived();
+        assert_eq!(cuboid.inverse_mass, 0.0);
+        assert_eq!(cuboid.inverse_inertia, Mat3::zero());
+    }
+
+    #[test]
+    fn test_runtime_settings() {
+        let mut world = World::new(
+            Scene::Grid,
+            1,
+            WorldSettings {
+                gravity: Vec3::default(),
+                ..Default::default()
+            },
+        );
+        world.update();
+        let body = world.bodies().find(|x| !x.frozen).unwrap();
+        assert_eq!(body.velocity, Vec3::default());
+
+        world.settings.gravity.y = -1.0;
+        world.settings.dt = 0.5;
+        world.update();
+        let body = world.bodies().find(|x| !x.frozen).unwrap();
+        assert!((body.velocity.y + 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_stack_sleeps_and_wakes() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        let bottom = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.5,
+                z: 0.0,
+            },
+            ..Default::default()
+        });
+        let top = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 1.5,
+                z: 0.0,
+            },
+            ..Default::default()
+        });
+        for _ in 0..360 {
+            world.update();
+        }
+        assert!(world.body(bottom).unwrap().asleep);
+        assert!(world.body(top).unwrap().asleep);
+        assert_eq!(world.update().bodies_awake, 0);
+
+        //pushing the bottom one wakes the whole stack
+        world.apply_impulse(
+            bottom,
+            Vec3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            world.body(bottom).unwrap().position,
+        );
+        assert!(!world.body(top).unwrap().asleep);
+        assert!(world.body(bottom).unwrap().velocity.x > 0.0);
+        assert_eq!(world.update().bodies_awake, 2);
+
+        //turning sleeping off wakes whatever's still asleep, and bodies touched while it's off
+        //don't pile up waiting to be woken
+        for _ in 0..360 {
+            world.update();
+        }
+        assert_eq!(world.update().bodies_awake, 0);
+        world.settings.allow_sleeping = false;
+        world.update();
+        assert!(!world.body(bottom).unwrap().asleep && !world.body(top).unwrap().asleep);
+        for _ in 0..10 {
+            world.update();
+        }
+        assert!(world.to_wake.is_empty());
+        assert_eq!(world.update().bodies_awake, 2);
+    }
+
+    #[test]
+    fn test_removing_support_wakes_stack() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        let platform = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 5.0,
+                z: 0.0,
+            },
+            frozen: true,
+            ..Default::default()
+        });
+        let stack: Vec<BodyHandle> = [6.0, 7.0]
+            .into_iter()
+            .map(|y| {
+                world.add_body(Cuboid {
+                    position: Vec3 { x: 0.0, y, z: 0.0 },
+                    ..Default::default()
+                })
+            })
+            .collect();
+        for _ in 0..360 {
+            world.update();
+        }
+        assert!(stack.iter().all(|&x| world.body(x).unwrap().asleep));
+
+        //the platform never sleeps itself, but the stack resting on it still has to fall
+        world.remove_body(platform);
+        assert!(stack.iter().all(|&x| !world.body(x).unwrap().asleep));
+        for _ in 0..60 {
+            world.update();
+        }
+        assert!(world.body(stack[0]).unwrap().position.y < 5.5);
+    }
+
+    #[test]
+    fn test_warm_start_cache() {
+        let mut world = World::new(
+            Scene::Grid,
+            0,
+            WorldSettings {
+                allow_sleeping: false,
+                ..Default::default()
+            },
+        );
+        let handle = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.5,
+                z: 0.0,
+            },
+            ..Default::default()
+        });
+        for _ in 0..180 {
+            world.update();
+        }
+        let key = (handle, world.static_bodies[0], (0, 0));
+        let ids = |world: &World| {
+            let mut ids: Vec<u32> = world.contact_cache[&key]
+                .iter()
+                .flatten()
+                .map(|x| x.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let before = ids(&world);
+        world.update();
+        //resting contacts keep their ids, so last tick's impulses find them again
+        assert_eq!(before.len(), 4);
+        assert_eq!(before, ids(&world));
+        //and between them they hold up exactly the box's weight
+        let body = world.body(handle).unwrap();
+        let total: f32 = world.contact_cache[&key]
+            .iter()
+            .flatten()
+            .map(|x| x.normal_impulse)
+            .sum();
+        let weight = body.get_mass() * 9.81 * world.settings.dt;
+        assert!((total - weight).abs() < weight * 0.05);
+    }
+
+    #[test]
+    fn test_ccd_and_speculative_contacts_stop_tunnelling() {
+        let run = |ccd, speculative_contacts| {
+            let mut world = World::new(
+                Scene::Grid,
+                0,
+                WorldSettings {
+                    speculative_contacts,
+                    ..Default::default()
+                },
+            );
+            //thin wall, a 0.2m cube at 290ms^-1 moves 1.6m per tick and steps straight over it
+            world.add_body(Cuboid {
+                position: Vec3 {
+                    x: 5.0,
+                    y: 2.0,
+                    z: 0.0,
+                },
+                scale: Vec3 {
+                    x: 0.05,
+                    y: 4.0,
+                    z: 4.0,
+                },
+                frozen: true,
+                ..Default::default()
+            });
+            let projectile = world.add_body(Cuboid {
+                position: Vec3 {
+                    x: 0.0,
+                    y: 2.0,
+                    z: 0.0,
+                },
+                scale: Vec3 {
+                    x: 0.2,
+                    y: 0.2,
+                    z: 0.2,
+                },
+                velocity: Vec3 {
+                    x: 290.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                ccd,
+                ..Default::default()
+            });
+            for _ in 0..10 {
+                world.update();
+            }
+            world.body(projectile).unwrap().position.x
+        };
+        assert!(run(false, false) > 5.0);
+        //bounced off instead
+        assert!(run(true, false) < 5.0);
+        //the fattened aabb reaches the wall a tick early, which is enough on its own here
+        assert!(run(false, true) < 5.0);
+    }
+
+    #[test]
+    fn test_sphere_rolls_down_slope() {
+        let mut world = World::new(
+            Scene::Grid,
+            0,
+            WorldSettings {
+                allow_sleeping: false,
+                ..Default::default()
+            },
+        );
+        let slope = Quaternion::from_angle(
+            &Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            0.2,
+        );
+        world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 5.0,
+                z: 0.0,
+            },
+            scale: Vec3 {
+                x: 20.0,
+                y: 1.0,
+                z: 4.0,
+            },
+            rotation: slope,
+            frozen: true,
+            ..Default::default()
+        });
+        let up = Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        }
+        .rotate(slope);
+        let ball = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 5.0,
+                z: 0.0,
+            } + up * 1.0,
+            shape: Shape::Sphere { radius: 0.5 },
+            ..Default::default()
+        });
+        for _ in 0..180 {
+            world.update();
+        }
+        let body = world.body(ball).unwrap();
+        //downhill is -x, and it's rolling rather than sliding, so the contact point is at rest
+        assert!(body.velocity.x < -1.0);
+        let contact_velocity = body.velocity + body.angular_velocity.cross(&(up * -0.5));
+        assert!(contact_velocity.mag() < 0.05 * body.velocity.mag());
+    }
+
+    #[test]
+    fn test_capsule_settles_on_its_side() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        //dropped end first, slightly off vertical, so it topples
+        let capsule = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            },
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                0.3,
+            ),
+            shape: Shape::Capsule {
+                radius: 0.25,
+                half_height: 0.5,
+            },
+            ..Default::default()
+        });
+        for _ in 0..720 {
+            world.update();
+        }
+        let body = world.body(capsule).unwrap();
+        assert!(body.asleep);
+        assert!((body.position.y - 0.25).abs() < 0.01);
+        assert!(body.face_axes[1].y.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_wedge_settles_on_a_face() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        let mut points = Vec::new();
+        for z in [-0.5, 0.5] {
+            for (x, y) in [(-0.6, -0.3), (0.6, -0.3), (-0.6, 0.3)] {
+                points.push(Vec3 { x, y, z });
+            }
+        }
+        let wedge = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            },
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+                0.7,
+            ),
+            shape: Shape::ConvexHull(std::sync::Arc::new(ConvexHull::new(&points).unwrap())),
+            ..Default::default()
+        });
+        for _ in 0..720 {
+            world.update();
+        }
+        let body = world.body(wedge).unwrap();
+        assert!(body.asleep);
+        let Shape::ConvexHull(hull) = &body.shape else {
+            unreachable!();
+        };
+        //all four corners of one of the rectangular faces on the floor
+        let mut heights: Vec<f32> = hull
+            .vertices
+            .iter()
+            .map(|x| (body.position + x.rotate(body.rotation)).y)
+            .collect();
+        heights.sort_by(f32::total_cmp);
+        assert!(heights[..4].iter().all(|x| x.abs() < 0.01));
+    }
+
+    #[test]
+    fn test_box_slides_across_mesh_edges() {
+        let mut world = World::new(
+            Scene::Grid,
+            0,
+            WorldSettings {
+                allow_sleeping: false,
+                ..Default::default()
+            },
+        );
+        //a raised flat strip cut into half unit cells, so the box crosses dozens of internal
+        //edges, any of which would trip it if it leaned a contact normal over them
+        let (n, cell) = (24, 0.5);
+        let mut vertices = Vec::new();
+        for i in 0..=n {
+            for j in 0..=4 {
+                vertices.push(Vec3 {
+                    x: i as f32 * cell,
+                    y: 0.0,
+                    z: j as f32 * cell - 1.0,
+                });
+            }
+        }
+        let mut triangles = Vec::new();
+        for i in 0..n {
+            for j in 0..4 {
+                let at = |i: usize, j: usize| i * 5 + j;
+                triangles.push([at(i, j), at(i, j + 1), at(i + 1, j + 1)]);
+                triangles.push([at(i, j), at(i + 1, j + 1), at(i + 1, j)]);
+            }
+        }
+        world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            },
+            shape: Shape::TriMesh(std::sync::Arc::new(
+                TriMesh::new(vertices, &triangles).unwrap(),
+            )),
+            ..Default::default()
+        });
+        let handle = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 1.0,
+                y: 2.5,
+                z: 0.0,
+            },
+            velocity: Vec3 {
+                x: 6.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            ..Default::default()
+        });
+        for _ in 0..240 {
+            world.update();
+            let body = world.body(handle).unwrap();
+            assert!(body.angular_velocity.mag() < 0.1);
+            assert!((body.position.y - 2.5).abs() < 0.02);
+        }
+        let body = world.body(handle).unwrap();
+        assert!(body.position.x > 4.0);
+        assert!(body.velocity.mag() < 0.1);
+    }
+
+    #[test]
+    fn test_ball_rolls_into_heightfield_valley() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        //a v shaped valley running along z, its floor at x = 0 and 1m up
+        let (columns, rows) = (17, 9);
+        let heights = (0..columns)
+            .flat_map(|i| (0..rows).map(move |_| 1.0 + (i as f32 - 8.0).abs() * 0.25))
+            .collect();
+        world.add_body(Cuboid {
+            shape: Shape::Heightfield(std::sync::Arc::new(
+                Heightfield::new(heights, columns, rows, 0.5).unwrap(),
+            )),
+            ..Default::default()
+        });
+        let ball = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 2.5,
+                y: 3.0,
+                z: 0.0,
+            },
+            shape: Shape::Sphere { radius: 0.5 },
+            ..Default::default()
+        });
+        for _ in 0..1800 {
+            world.update();
+        }
+        let body = world.body(ball).unwrap();
+        assert!(body.asleep);
+        //resting in the crease on both slopes, each 0.5 / cos(atan(0.5)) above it
+        assert!(body.position.x.abs() < 0.05);
+        assert!((body.position.y - (1.0 + 0.5 * 1.25f32.sqrt())).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_ball_stops_at_wall_plane() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        //a wall facing -x, 3m along
+        world.add_body(Cuboid {
+            position: Vec3 {
+                x: 3.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            shape: Shape::Plane {
+                normal: Vec3 {
+                    x: -1.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                offset: 0.0,
+            },
+            ..Default::default()
+        });
+        let ball = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.5,
+                z: 0.0,
+            },
+            velocity: Vec3 {
+                x: 4.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            shape: Shape::Sphere { radius: 0.5 },
+            ..Default::default()
+        });
+        //it reaches the wall without going into it, then bounces back off
+        let mut furthest = 0.0f32;
+        for _ in 0..300 {
+            world.update();
+            furthest = furthest.max(world.body(ball).unwrap().position.x);
+            assert!(furthest < 2.5 + 0.02);
+        }
+        assert!(furthest > 2.45);
+        assert!(world.body(ball).unwrap().velocity.x < 0.0);
+    }
+
+    #[test]
+    fn test_table_lands_on_its_legs() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        let block = |scale: Vec3, position: Vec3| Child {
+            scale,
+            position,
+            ..Default::default()
+        };
+        let leg = Vec3 {
+            x: 0.1,
+            y: 0.7,
+            z: 0.1,
+        };
+        let mut children = vec![block(
+            Vec3 {
+                x: 1.6,
+                y: 0.1,
+                z: 1.0,
+            },
+            Vec3 {
+                x: 0.0,
+                y: 0.75,
+                z: 0.0,
+            },
+        )];
+        for (x, z) in [(-0.7, -0.4), (-0.7, 0.4), (0.7, -0.4), (0.7, 0.4)] {
+            children.push(block(leg, Vec3 { x, y: 0.35, z }));
+        }
+        let table = Compound::new(children).unwrap();
+        //the top's 0.16m^3 at 0.75 and the legs' 0.028m^3 at 0.35
+        let centre = (0.16 * 0.75 + 0.028 * 0.35) / 0.188;
+        assert!((table.children[0].position.y - (0.75 - centre)).abs() < 1e-5);
+        let handle = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: centre + 0.5,
+                z: 0.0,
+            },
+            shape: Shape::Compound(std::sync::Arc::new(table)),
+            ..Default::default()
+        });
+        assert!((world.body(handle).unwrap().get_mass() - 0.188).abs() < 1e-5);
+        let mut legs_down = false;
+        for _ in 0..600 {
+            //each leg touches the floor on its own
+            legs_down |= world.update().collisions == 4;
+        }
+        assert!(legs_down);
+        let body = world.body(handle).unwrap();
+        assert!(body.asleep);
+        assert!((body.position.y - centre).abs() < 0.01);
+        assert!(body.face_axes[1].y > 0.999);
+    }
+
+    #[test]
+    fn test_weighted_capsule_rights_itself() {
+        let mut world = World::new(Scene::Grid, 0, WorldSettings::default());
+        //lying on their sides, one weighted low in its bottom cap like a roly poly toy
+        let (radius, half_height) = (0.4, 0.3);
+        //with the lower cap just touching
+        let capsule = |z: f32, mass_offset: Vec3| Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: radius + half_height * 1.5f32.cos(),
+                z,
+            },
+            rotation: Quaternion::from_angle(
+                &Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+                1.5,
+            ),
+            shape: Shape::Capsule {
+                radius,
+                half_height,
+            },
+            mass_offset,
+            ..Default::default()
+        };
+        let weighted = world.add_body(capsule(
+            0.0,
+            Vec3 {
+                x: 0.0,
+                y: -(half_height + radius / 2.0),
+                z: 0.0,
+            },
+        ));
+        let plain = world.add_body(capsule(3.0, Vec3::default()));
+        let start = world.body(weighted).unwrap().centre_of_mass().y;
+        //nothing damps it, so it rocks up through standing and over like a pendulum. its centre
+        //of mass never gets any higher than it started
+        let mut upright = false;
+        for _ in 0..360 {
+            world.update();
+            let body = world.body(weighted).unwrap();
+            upright |= body.face_axes[1].y > 0.99;
+            assert!(body.centre_of_mass().y < start + 0.01);
+            assert!(world.body(plain).unwrap().face_axes[1].y < 0.1);
+        }
+        assert!(upright);
+    }
+
+    #[test]
+    fn test_spins_about_centre_of_mass() {
+        let settings = WorldSettings {
+            gravity: Vec3::default(),
+            ..Default::default()
+        };
+        let mut world = World::new(Scene::Grid, 0, settings);
+        let offset = Vec3 {
+            x: 0.5,
+            y: 0.0,
+            z: 0.0,
+        };
+        let handle = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 10.0,
+                z: 0.0,
+            },
+            mass_offset: offset,
+            ..Default::default()
+        });
+        let centre = world.body(handle).unwrap().centre_of_mass();
+        //pushed through the centre of mass it only moves, pushed anywhere else it spins too
+        world.apply_impulse(handle, offset, centre);
+        assert!(world.body(handle).unwrap().angular_velocity.mag() < EPSILON);
+        world.body_mut(handle).unwrap().velocity = Vec3::default();
+        let up = Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        world.apply_impulse(handle, up, centre - offset);
+        let body = world.body(handle).unwrap();
+        assert!(body.angular_velocity.z < -EPSILON);
+        let velocity = body.velocity;
+        for _ in 0..180 {
+            world.update();
+        }
+        //the geometric centre swings round it, the centre of mass goes straight
+        let body = world.body(handle).unwrap();
+        assert!((body.centre_of_mass() - (centre + velocity)).mag() < 1e-3);
+        assert!((body.position - body.centre_of_mass()).mag() - 0.5 < 1e-4);
+        assert!((body.position - (centre + velocity - offset)).mag() > 0.1);
+    }
+
+    #[test]
+    fn test_add_remove_body() {
+        let mut world = World::new(Scene::Grid, 4, WorldSettings::default());
+        let handle = world.add_body(Cuboid {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.5,
+                z: 0.0,
+            },
+            ..Default::default()
+        });
+        let index = world.body(handle).unwrap().index;
+        assert_eq!(index, handle.index());
+        assert_eq!(world.bodies().count(), 6);
+        world.update();
+
+        assert!(world.remove_body(handle).is_some());
+        assert!(world.body(handle).is_none());
+        assert!(world.remove_body(handle).is_none());
+        world.update(); //grid and solver must tolerate the hole
+
+        let reused = world.add_body(Cuboid::default());
+        assert_eq!(reused.index(), index);
+        assert!(world.body(handle).is_none());
+        world.update();
+
+        //nothing's left to stand on once the floor goes
+        let resting = world.add_body(Cuboid {
+            position: Vec3 {
+                x: -5.0,
+                y: 0.5,
+                z: 0.0,
+            },
+            ..Default::default()
+        });
+        for _ in 0..60 {
+            world.update();
+        }
+        assert!(world.body(resting).unwrap().position.y > 0.49);
+        let floor = world.static_bodies[0];
+        assert!(world.remove_body(floor).is_some());
+        assert!(world.static_bodies.is_empty());
+        for _ in 0..60 {
+            world.update();
+        }
+        assert!(world.body(resting).unwrap().position.y < 0.4);
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+//any rigid body, shape says what it collides as
+#[derive(Debug, Clone)]
 pub struct Cuboid {
-    pub index: usize,
-    pub position: Vec3, //centre
+    pub index: usize,   //slot in World::instances
+    pub position: Vec3, //geometric centre, which is what gets collided and drawn
     pub rotation: Quaternion,
-    pub velocity: Vec3,         //ms^-1
+    pub velocity: Vec3,         //ms^-1, of the centre of mass
     pub angular_velocity: Vec3, //rads^-1
-    pub scale: Vec3,            //local
-    pub corners: [Vec3; 8],
+    pub shape: Shape,
+    pub scale: Vec3,        //local, cuboids only
+    pub corners: [Vec3; 8], //of the local bounding box
     pub aabb: AABB,
     pub frozen: bool,
     pub face_axes: [Vec3; 3],
     pub density: f32,
+    pub material: Material,
+    pub mass_override: Option<f32>,     //kg, replaces volume * density
+    pub inertia_override: Option<Vec3>, //local principal moments about the centre of mass, kgm^2
+    //local, from position to the centre of mass, for bodies weighted to one side like a hammer.
+    //the inertia is taken to be about it as it stands, there's no telling how the weight's spread
+    pub mass_offset: Vec3,
+    pub inverse_mass: f32,
+    pub inverse_inertia: Mat3, //world space
+    pub asleep: bool,
+    pub sleep_timer: f32,      //s spent under the sleep thresholds
+    pub sleep_island: usize,   //only meaningful while asleep
+    pub ccd: bool,             //sweep fast moves so it can't tunnel through thin bodies
+    pub children: Vec<Cuboid>, //compounds only, each child where it sits as of update_derived()
 }
 impl Cuboid {
+    //i.e. gets integrated and solved
+    pub fn is_awake(&self) -> bool {
+        !self.frozen && !self.asleep
+    }
+    //how far it could move towards something this tick, linear plus the fastest a corner spins
+    //about the centre of mass
+    pub fn speculative_margin(&self, dt: f32) -> f32 {
+        let reach = self.shape.bounding_radius(self.scale) + self.mass_offset.mag();
+        (self.velocity.mag() + self.angular_velocity.mag() * reach) * dt
+    }
+    //world space, what it spins about and impulses' lever arms are measured from
+    pub fn centre_of_mass(&self) -> Vec3 {
+        self.position + self.mass_offset.rotate(self.rotation)
+    }
+    pub fn extents(&self) -> Vec3 {
+        self.shape.extents(self.scale)
+    }
+    //(normal, offset) of a plane body in world space, None for anything else
+    pub fn plane(&self) -> Option<(Vec3, f32)> {
+        let Shape::Plane { normal, offset } = self.shape else {
+            return None;
+        };
+        let length = normal.mag();
+        let normal = (normal / length).rotate(self.rotation);
+        Some((normal, offset / length + self.position.dot(&normal)))
+    }
+    //one of a compound's children as a frozen body of its own, where it sits on this one. it
+    //keeps this body's index, so contacts with it land on this body
+    pub fn child(&self, child: &Child) -> Cuboid {
+        let mut body = Cuboid {
+            index: self.index,
+            position: self.position + child.position.rotate(self.rotation),
+            rotation: self.rotation * child.rotation,
+            shape: child.shape.clone(),
+            scale: child.scale,
+            frozen: true,
+            ..Default::default()
+        };
+        body.update_derived();
+        body
+    }
+    pub fn smallest_extent(&self) -> f32 {
+        if let Shape::Compound(compound) = &self.shape {
+            return compound.thinnest;
+        }
+        let extents = self.extents();
+        extents.x.min(extents.y).min(extents.z)
+    }
     pub fn update_derived(&mut self) {
         self.calc_corners();
         self.calc_aabb();
         self.calc_face_axes();
+        //the solver reads these per contact per iteration
+        self.inverse_mass = self.get_inverse_mass();
+        self.inverse_inertia = self.get_inverse_moment_of_inertia();
+        //every pair the compound is in collides against these, so they're placed once a tick
+        self.children = match &self.shape {
+            Shape::Compound(compound) => compound.children.iter().map(|x| self.child(x)).collect(),
+            _ => Vec::new(),
+        };
     }
     pub fn get_all_face_axes(&self) -> [Vec3; 6] {
         [
@@ -257,20 +1635,16 @@ impl Cuboid {
         if m==f32::INFINITY {
             return Mat3::zero();
         }
-        let Vec3{x,y,z}=self.scale;
-        let ixx = (1.0/12.0) * m * (y*y + z*z);
-        let iyy = (1.0/12.0) * m * (x*x + z*z);
-        let izz = (1.0/12.0) * m * (x*x + y*y);
-        let local = Mat3 {
-            array: [
-                1.0/ixx,0.0,0.0,
-                0.0,1.0/iyy,0.0,
-                0.0,0.0,1.0/izz
-            ]
+        let inertia=match self.inertia_override {
+            Some(principal)=>Mat3::diagonal(principal),
+            None=>self.shape.unit_inertia(self.scale).scale(m),
+        };
+        let Some(local)=inertia.inverse() else {
+            return Mat3::zero();
         };
         let rotation_mat=self.rotation.to_mat3();
         let transpose=rotation_mat.transpose();
-        rotation_mat*local*transpose //OPTIMIZE: cache in update_derived()
+        rotation_mat*local*transpose
 
     }
     fn calc_face_axes(&mut self) {
@@ -280,27 +1654,29 @@ impl Cuboid {
             GLOBAL_AXES[2].rotate(self.rotation).normalize().unwrap(),
         ];
     }
+    pub fn get_mass(&self) -> f32 {
+        if self.frozen {
+            f32::INFINITY
+        } else {
+            self.mass_override
+                .unwrap_or(self.shape.volume(self.scale) * self.density)
+        }
+    }
     pub fn get_inverse_mass(&self) -> f32 {
         if self.frozen {
             0.0
         } else {
-            1.0 / (self.scale.mag() * self.density)
+            1.0 / self.get_mass()
         }
     }
     fn calc_corners(&mut self) {
-        let delta: Vec3 = self.scale / 2.0;
+        let (min, max) = self.shape.bounds(self.scale);
         let mut index = 0;
         let mut ans = [Vec3::default(); 8];
-        for i in [-1.0, 1.0] {
-            for j in [-1.0, 1.0] {
-                for k in [-1.0, 1.0] {
-                    ans[index] = self.position
-                        + (Vec3 {
-                            x: i * delta.x,
-                            y: j * delta.y,
-                            z: k * delta.z,
-                        }
-                        .rotate(self.rotation));
+        for x in [min.x, max.x] {
+            for y in [min.y, max.y] {
+                for z in [min.z, max.z] {
+                    ans[index] = self.position + (Vec3 { x, y, z }.rotate(self.rotation));
                     index += 1;
                 }
             }
@@ -309,6 +1685,47 @@ impl Cuboid {
     }
 
     pub fn calc_aabb(&mut self) {
+        //unbounded, except below a plane facing straight along an axis
+        if let Some((normal, offset)) = self.plane() {
+            let mut aabb = AABB::new(
+                Vec3 {
+                    x: f32::NEG_INFINITY,
+                    y: f32::NEG_INFINITY,
+                    z: f32::NEG_INFINITY,
+                },
+                Vec3 {
+                    x: f32::INFINITY,
+                    y: f32::INFINITY,
+                    z: f32::INFINITY,
+                },
+            );
+            for (axis, min, max) in [
+                (normal.x, &mut aabb.min.x, &mut aabb.max.x),
+                (normal.y, &mut aabb.min.y, &mut aabb.max.y),
+                (normal.z, &mut aabb.min.z, &mut aabb.max.z),
+            ] {
+                if axis > 1.0 - EPSILON {
+                    *max = offset;
+                } else if axis < -1.0 + EPSILON {
+                    *min = -offset;
+                }
+            }
+            self.aabb = aabb;
+            return;
+        }
+        //the rotated bounding box's corners would overestimate it
+        if let Shape::Sphere { radius } = self.shape {
+            let radius = Vec3 {
+                x: radius,
+                y: radius,
+                z: radius,
+            };
+            self.aabb = AABB {
+                min: self.position - radius,
+                max: self.position + radius,
+            };
+            return;
+        }
         let mut min_x = f32::INFINITY;
         let mut min_y = f32::INFINITY;
         let mut min_z = f32::INFINITY;
@@ -336,15 +1753,20 @@ impl Cuboid {
             },
         }
     }
+    pub fn to_raw(&self) -> CuboidRaw {
+        self.to_raw_at(self.position, self.extents())
+    }
+    //meshes are unit sized, so scale is the size it's drawn at. shapes drawn as more than one
+    //mesh place each part themselves
     #[rustfmt::skip]
-    pub fn to_raw(&self)->CuboidRaw {
+    pub fn to_raw_at(&self, position: Vec3, scale: Vec3)->CuboidRaw {
         let rotation_matrix=self.rotation.to_mat3();
         CuboidRaw {
             model:[
-                    rotation_matrix.array[0]*self.scale.x, rotation_matrix.array[1]*self.scale.x, rotation_matrix.array[2]*self.scale.x, 0.0,
-                    rotation_matrix.array[3]*self.scale.y, rotation_matrix.array[4]*self.scale.y, rotation_matrix.array[5]*self.scale.y, 0.0,
-                    rotation_matrix.array[6]*self.scale.z, rotation_matrix.array[7]*self.scale.z, rotation_matrix.array[8]*self.scale.z, 0.0,
-                    self.position.x,        self.position.y,        self.position.z,                               1.0,
+                    rotation_matrix.array[0]*scale.x, rotation_matrix.array[1]*scale.x, rotation_matrix.array[2]*scale.x, 0.0,
+                    rotation_matrix.array[3]*scale.y, rotation_matrix.array[4]*scale.y, rotation_matrix.array[5]*scale.y, 0.0,
+                    rotation_matrix.array[6]*scale.z, rotation_matrix.array[7]*scale.z, rotation_matrix.array[8]*scale.z, 0.0,
+                    position.x,        position.y,        position.z,                               1.0,
             ]
         }
     }
@@ -361,16 +1783,28 @@ impl Default for Cuboid {
             rotation: Quaternion::default(),
             velocity: Vec3::default(),
             angular_velocity: Vec3::default(),
+            shape: Shape::Cuboid,
             corners: [Vec3::default(); 8],
             aabb: AABB::default(),
             face_axes: [Vec3::default(); 3],
             frozen: false,
             index: 0,
             density: 1.0,
+            material: Material::default(),
+            mass_override: None,
+            inertia_override: None,
+            mass_offset: Vec3::default(),
+            inverse_mass: 0.0,
+            inverse_inertia: Mat3::zero(),
+            asleep: false,
+            sleep_timer: 0.0,
+            sleep_island: 0,
+            ccd: false,
+            children: Vec::new(),
         }
     }
 }
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct AABB {
     pub min: Vec3,
     pub max: Vec3,
@@ -385,6 +1819,28 @@ impl AABB {
         Self { min, max }
     }
 
+    //the smallest one around all of them
+    pub fn around(points: impl IntoIterator<Item = Vec3>) -> AABB {
+        let mut min = Vec3 {
+            x: f32::INFINITY,
+            y: f32::INFINITY,
+            z: f32::INFINITY,
+        };
+        let mut max = -min;
+        for point in points {
+            min = Vec3 {
+                x: min.x.min(point.x),
+                y: min.y.min(point.y),
+                z: min.z.min(point.z),
+            };
+            max = Vec3 {
+                x: max.x.max(point.x),
+                y: max.y.max(point.y),
+                z: max.z.max(point.z),
+            };
+        }
+        AABB { min, max }
+    }
     pub fn get_dimensions(&self) -> Vec3 {
         Vec3 {
             x: self.max.x - self.min.x,
@@ -396,6 +1852,81 @@ impl AABB {
         !((self.min.x > other.max.x || self.min.y > other.max.y || self.min.z > other.max.z)
             || (self.max.x < other.min.x || self.max.y < other.min.y || self.max.z < other.min.z))
     }
+    pub fn expand(&self, margin: f32) -> AABB {
+        let margin = Vec3 {
+            x: margin,
+            y: margin,
+            z: margin,
+        };
+        AABB {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+    pub fn union(&self, other: &AABB) -> AABB {
+        AABB {
+            min: Vec3 {
+                x: self.min.x.min(other.min.x),
+                y: self.min.y.min(other.min.y),
+                z: self.min.z.min(other.min.z),
+            },
+            max: Vec3 {
+                x: self.max.x.max(other.max.x),
+                y: self.max.y.max(other.max.y),
+                z: self.max.z.max(other.max.z),
+            },
+        }
+    }
+    pub fn translate(&self, offset: Vec3) -> AABB {
+        AABB {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+    //fraction of displacement self can move before touching other (slab test), None if it never
+    //does within the whole displacement
+    pub fn time_of_entry(&self, displacement: Vec3, other: &AABB) -> Option<f32> {
+        let mut entry: f32 = 0.0;
+        let mut exit: f32 = 1.0;
+        for (min, max, other_min, other_max, d) in [
+            (
+                self.min.x,
+                self.max.x,
+                other.min.x,
+                other.max.x,
+                displacement.x,
+            ),
+            (
+                self.min.y,
+                self.max.y,
+                other.min.y,
+                other.max.y,
+                displacement.y,
+            ),
+            (
+                self.min.z,
+                self.max.z,
+                other.min.z,
+                other.max.z,
+                displacement.z,
+            ),
+        ] {
+            if d.abs() < EPSILON {
+                if max < other_min || min > other_max {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (other_min - max) / d;
+            let t1 = (other_max - min) / d;
+            entry = entry.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+            if entry > exit {
+                return None;
+            }
+        }
+        Some(entry)
+    }
 }
 
 #[repr(C)]